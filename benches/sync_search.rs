@@ -0,0 +1,93 @@
+//! Compares the byte-aligned sync search of `Buffer::set_pos_next_frame` with the previous search,
+//! which shifted a 16-bit window through the data one bit at a time, and the full frame scan with
+//! the header-only one. Run with `cargo bench`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use mp3_info::buffer::Buffer;
+
+const SAMPLE: &str = "mp3-examples/test_data_100kb.mp3";
+const ITERATIONS: u32 = 50;
+
+/// Previous search: every bit position is a candidate.
+fn bitwise_next_frame(buffer: &mut Buffer) -> Option<usize> {
+    let mut bits = buffer.get_bits(16).ok()?;
+    loop {
+        if bits & 0xffe6 == 0xffe2 && bits & 0x18 != 0x08 {
+            buffer.move_pos(-16).ok()?;
+            return Some(buffer.pos);
+        }
+
+        bits = ((bits << 1) & 0xffff) | buffer.get_bits(1).ok()?;
+    }
+}
+
+/// Counts every sync candidate of the data, the worst case of a search through junk.
+fn count_syncs(buffer: &mut Buffer, next: fn(&mut Buffer) -> Option<usize>) -> usize {
+    buffer.pos = 0;
+    let mut count = 0;
+    while next(buffer).is_some() {
+        count += 1;
+        if buffer.move_pos(8).is_err() {
+            break;
+        }
+    }
+
+    count
+}
+
+fn time(name: &str, mut run: impl FnMut() -> usize) -> Duration {
+    let result = run();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(run());
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+
+    println!("{name:<40} {elapsed:>12.2?}  ({result})");
+    elapsed
+}
+
+fn main() {
+    let data = Buffer::create_buffer_from_file(SAMPLE).data.to_vec();
+
+    // The sample with 64KiB of pseudo-random junk in front, as left by a broken download.
+    let mut seed = 0x2545f491u32;
+    let mut junk: Vec<u8> = (0..64 * 1024)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+    junk.extend_from_slice(&data);
+
+    for (label, data) in [("sample", data), ("sample after junk", junk)] {
+        println!("{label}, {} bytes:", data.len());
+        let mut buffer = Buffer::create_buffer_from_data(data);
+
+        let bitwise = time("  all sync candidates, bit by bit", || {
+            count_syncs(&mut buffer, bitwise_next_frame)
+        });
+        let aligned = time("  all sync candidates, byte-aligned", || {
+            count_syncs(&mut buffer, |buffer| buffer.set_pos_next_frame().ok())
+        });
+        println!(
+            "  speedup: {:.1}x",
+            bitwise.as_secs_f64() / aligned.as_secs_f64()
+        );
+
+        time("  extract_frames", || {
+            buffer.pos = 0;
+            buffer.extract_frames().len()
+        });
+        time("  scan_headers", || {
+            buffer.pos = 0;
+            buffer.scan_headers().len()
+        });
+    }
+}
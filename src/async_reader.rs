@@ -0,0 +1,206 @@
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    buffer::{Buffer, MAX_FREE_FORMAT_LENGTH},
+    error::ErrorType,
+    frame::Frame,
+    tag,
+};
+
+/// Bytes requested from the reader at a time.
+const READ_SIZE: usize = 8192;
+
+/// A frame read from a stream, with its bytes. Offsets are counted from the start of the stream.
+#[derive(Debug)]
+pub struct StreamFrame {
+    pub frame: Frame,
+    pub data: Vec<u8>,
+}
+
+/// Reads frames from an [`AsyncRead`] as their bytes arrive, skipping a leading ID3v2 tag and
+/// junk between frames like [`Buffer::extract_frames`]. Only the current frame is kept in memory.
+pub struct AsyncFrameReader<R> {
+    reader: R,
+    /// Bytes read but not yet returned, starting at `offset` in the stream.
+    pending: Vec<u8>,
+    offset: usize,
+    /// Bytes of the ID3v2 tag still to drop, `None` until the tag header has been read.
+    skip: Option<usize>,
+    free_format_length: Option<usize>,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncFrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Vec::new(),
+            offset: 0,
+            skip: None,
+            free_format_length: None,
+            eof: false,
+        }
+    }
+
+    /// Reads more bytes into `pending`. Returns false at the end of the stream.
+    async fn fill(&mut self) -> std::io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        let mut chunk = [0; READ_SIZE];
+        let read = self.reader.read(&mut chunk).await?;
+        self.pending.extend_from_slice(&chunk[..read]);
+        self.eof = read == 0;
+
+        Ok(!self.eof)
+    }
+
+    fn consume(&mut self, bytes: usize) -> Vec<u8> {
+        self.offset += bytes;
+        self.pending.drain(..bytes).collect()
+    }
+
+    /// Parses the frame at the start of `pending` with [`Frame::create_from_buffer`].
+    fn parse(&mut self) -> Result<Frame, ErrorType> {
+        let mut buffer = Buffer::create_buffer_from_data(std::mem::take(&mut self.pending));
+        buffer.free_format_length = self.free_format_length;
+
+        let frame = Frame::create_from_buffer(&mut buffer).and_then(|frame| {
+            frame.header().validate_header()?;
+            Ok(frame)
+        });
+
+        self.free_format_length = buffer.free_format_length;
+        self.pending = buffer.data.into_vec();
+
+        frame
+    }
+
+    /// Next complete frame, or `None` at the end of the stream or at a truncated last frame.
+    pub async fn next_frame(&mut self) -> std::io::Result<Option<StreamFrame>> {
+        while self.skip.is_none() {
+            if self.pending.len() >= 10 || !self.fill().await? {
+                self.skip = Some(tag::id3v2_declared_length(&self.pending));
+            }
+        }
+
+        loop {
+            let skip = self.skip.unwrap_or(0).min(self.pending.len());
+            self.consume(skip);
+            self.skip = self.skip.map(|bytes| bytes - skip);
+            if self.skip != Some(0) {
+                if !self.fill().await? {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            // Junk before the next sync is dropped, except for a possible partial header.
+            let mut buffer = Buffer::create_buffer_from_data(std::mem::take(&mut self.pending));
+            let sync = buffer.set_pos_next_frame();
+            self.pending = buffer.data.into_vec();
+
+            let Ok(sync) = sync else {
+                let junk = self.pending.len().saturating_sub(3);
+                self.consume(junk);
+                if !self.fill().await? {
+                    return Ok(None);
+                }
+                continue;
+            };
+            self.consume(sync);
+
+            match self.parse() {
+                Ok(frame) => {
+                    let length = frame.length_byte();
+                    while self.pending.len() < length {
+                        if !self.fill().await? {
+                            return Ok(None);
+                        }
+                    }
+
+                    let mut frame = frame;
+                    frame.move_offset(self.offset);
+                    let data = self.consume(length);
+
+                    return Ok(Some(StreamFrame { frame, data }));
+                }
+                // Not enough bytes yet for the side info, the main data it points to or, in free
+                // format, the next sync.
+                Err(ErrorType::OutOfIndex | ErrorType::Overflow | ErrorType::FreeFormat)
+                    if self.pending.len() < MAX_FREE_FORMAT_LENGTH + 4 && !self.eof =>
+                {
+                    self.fill().await?;
+                }
+                // False sync.
+                Err(_) => {
+                    self.consume(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// Sends `stream` in small writes and reads all its frames back.
+    async fn read_frames(stream: Vec<u8>) -> Vec<StreamFrame> {
+        let (mut writer, reader) = tokio::io::duplex(1000);
+        let send = async move {
+            for chunk in stream.chunks(333) {
+                writer.write_all(chunk).await.unwrap();
+            }
+        };
+
+        let receive = async move {
+            let mut reader = AsyncFrameReader::new(reader);
+            let mut received = Vec::new();
+            while let Some(frame) = reader.next_frame().await.unwrap() {
+                received.push(frame);
+            }
+            received
+        };
+
+        let ((), received) = tokio::join!(send, receive);
+        received
+    }
+
+    #[tokio::test]
+    async fn test_async_frame_reader() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let frames = buffer.extract_frames();
+
+        // An ID3v2 tag and junk in front.
+        let mut stream = b"ID3\x04\x00\x00\x00\x00\x00\x05tag..".to_vec();
+        stream.extend_from_slice(&[0xff, 0x00, 0x12]);
+        let prefix = stream.len();
+        stream.extend_from_slice(&buffer.data);
+
+        let received = read_frames(stream).await;
+        assert_eq!(received.len(), frames.len());
+        for (received, frame) in received.iter().zip(&frames) {
+            assert_eq!(received.frame.offset_byte(), prefix + frame.offset_byte());
+            assert_eq!(
+                received.frame.side_info().main_data_begin,
+                frame.side_info().main_data_begin
+            );
+            assert_eq!(received.frame.main_data_length(), frame.main_data_length());
+            assert_eq!(received.data, buffer.frame_data(frame));
+        }
+
+        // A tag larger than a read, holding frames of its own, is skipped whole.
+        let size = 3 * READ_SIZE;
+        let mut stream = b"ID3\x04\x00\x00".to_vec();
+        stream.extend((0..4).rev().map(|i| (size >> (7 * i)) as u8 & 0x7f));
+        stream.extend_from_slice(&buffer.data[..size]);
+        stream.extend_from_slice(&buffer.data);
+
+        let received = read_frames(stream).await;
+        assert_eq!(received.len(), frames.len());
+        assert_eq!(received[0].frame.offset_byte(), 10 + size);
+    }
+}
@@ -1,66 +1,258 @@
-use std::{fs, io::Read};
+use core::ops::Deref;
+
+#[cfg(feature = "alloc")]
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "std")]
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+#[cfg(feature = "std")]
+use memmap2::Mmap;
+
+#[cfg(feature = "std")]
+use crate::xing::{VbriHeader, XingHeader};
+use crate::{
+    error::ErrorType,
+    frame::{Frame, FrameRecord},
+    header::Header,
+};
+
+#[cfg(feature = "alloc")]
+use crate::tag;
+
+/// Bytes behind a [`Buffer`]: borrowed, read into memory or mapped from a file. They are not
+/// meant to change once the buffer is created, as the bit cache would not see it.
+pub enum Data<'a> {
+    Borrowed(&'a [u8]),
+    #[cfg(feature = "alloc")]
+    Owned(Vec<u8>),
+    #[cfg(feature = "std")]
+    Mapped(Mmap),
+    /// Shared between buffers, see [`Buffer::share`].
+    #[cfg(feature = "alloc")]
+    Shared(Arc<Data<'a>>),
+}
+
+#[cfg(feature = "alloc")]
+impl Data<'_> {
+    /// The bytes as a vector, copied unless they are owned.
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            Data::Owned(data) => data,
+            data => data.to_vec(),
+        }
+    }
+}
+
+impl Deref for Data<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Data::Borrowed(data) => data,
+            #[cfg(feature = "alloc")]
+            Data::Owned(data) => data,
+            #[cfg(feature = "std")]
+            Data::Mapped(map) => map,
+            #[cfg(feature = "alloc")]
+            Data::Shared(data) => data,
+        }
+    }
+}
 
-use crate::{error::ErrorType, frame::Frame};
+/// Longest read served by the bit cache: 64 bits minus up to 7 bits before the position in the
+/// first cached byte.
+pub const MAX_PEEK_BITS: u32 = 57;
 
-pub struct Buffer {
-    pub data: Vec<u8>,
+/// Free format frames go up to 640kb/sec, 2880 bytes at 32kHz. The next sync is not searched
+/// for further away.
+pub const MAX_FREE_FORMAT_LENGTH: usize = 2881;
+
+/// Checks the two header bytes following the `0xff` sync byte.
+fn is_header_start(b1: u8, b2: u8) -> bool {
+    b1 & 0xe6 == 0xe2 && b1 & 0x18 != 0x08 && b2 & 0xf0 != 0xf0 && b2 & 0x0c != 0x0c
+}
+
+pub struct Buffer<'a> {
+    pub data: Data<'a>,
     pub pos: usize,
     pub total_bits: usize,
+    /// Frame length of a free format stream without the padding slot, measured on its first
+    /// frame and carried forward to the next ones.
+    pub free_format_length: Option<usize>,
+    /// The 64 bits starting at bit `cache_start`, which is byte-aligned.
+    cache: u64,
+    cache_start: usize,
 }
 
-impl Buffer {
-    pub fn create_buffer_from_file(path: &str) -> Buffer {
-        let mut file = fs::File::open(path).expect("Cannot open mp3 file.");
+#[cfg(feature = "std")]
+impl Buffer<'static> {
+    pub fn create_buffer_from_file(path: &str) -> Self {
+        Self::try_create_buffer_from_file(path).expect("Cannot read data from file")
+    }
+
+    pub fn try_create_buffer_from_file(path: &str) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
         let mut data: Vec<u8> = Vec::new();
 
-        file.read_to_end(&mut data)
-            .expect("Cannot read data from file");
+        file.read_to_end(&mut data)?;
+
+        Ok(Self::create_buffer_from_data(data))
+    }
+
+    /// Maps the file into memory instead of reading it, so only the pages that are accessed get
+    /// loaded and frame data is borrowed straight from the mapping. The file must not be
+    /// truncated or modified while the buffer is alive.
+    pub fn create_buffer_from_mapped_file(path: &str) -> Self {
+        Self::try_create_buffer_from_mapped_file(path).expect("Cannot map mp3 file")
+    }
+
+    pub fn try_create_buffer_from_mapped_file(path: &str) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the mapping is read only; changes made to the file by other processes while it
+        // is mapped are not supported, as documented on `create_buffer_from_mapped_file`.
+        let map = unsafe { Mmap::map(&file) }?;
+
+        Ok(Self::create_buffer(Data::Mapped(map)))
+    }
+}
 
+#[cfg(feature = "alloc")]
+impl Buffer<'static> {
+    pub fn create_buffer_from_data(data: Vec<u8>) -> Self {
+        Self::create_buffer(Data::Owned(data))
+    }
+}
+
+impl<'a> Buffer<'a> {
+    /// Reads `data` in place, without allocating.
+    pub fn create_buffer_from_slice(data: &'a [u8]) -> Self {
+        Self::create_buffer(Data::Borrowed(data))
+    }
+
+    fn create_buffer(data: Data<'a>) -> Self {
         let total_bits = data.len() * 8;
 
         Self {
             data,
             pos: 0,
             total_bits,
+            free_format_length: None,
+            cache: 0,
+            cache_start: usize::MAX,
         }
     }
 
-    pub fn get_bits(&mut self, n: u32) -> Result<u32, ErrorType> {
-        if self.pos + n as usize >= self.total_bits {
-            return Err(ErrorType::OutOfIndex);
+    /// Another buffer over the same bytes, e.g. for another thread, starting at position 0.
+    /// Unless they are borrowed, the data of `self` is moved behind an `Arc` the first time.
+    pub fn share(&mut self) -> Self {
+        let data = match core::mem::replace(&mut self.data, Data::Borrowed(&[])) {
+            Data::Borrowed(data) => Data::Borrowed(data),
+            #[cfg(feature = "alloc")]
+            Data::Shared(shared) => Data::Shared(shared),
+            #[cfg(feature = "alloc")]
+            data => Data::Shared(Arc::new(data)),
+        };
+        self.data = match &data {
+            Data::Borrowed(data) => Data::Borrowed(data),
+            #[cfg(feature = "alloc")]
+            Data::Shared(shared) => Data::Shared(shared.clone()),
+            #[cfg(feature = "alloc")]
+            _ => unreachable!("data is borrowed or shared"),
+        };
+
+        Self::create_buffer(data)
+    }
+
+    /// Loads the 8 bytes starting at the byte holding `pos` into the bit cache, padding with
+    /// zeros past the end of the data.
+    fn refill(&mut self) {
+        let start = self.pos / 8;
+        let available = self.data.get(start..).unwrap_or_default();
+        let available = &available[..available.len().min(8)];
+
+        let mut bytes = [0; 8];
+        bytes[..available.len()].copy_from_slice(available);
+
+        self.cache = u64::from_be_bytes(bytes);
+        self.cache_start = start * 8;
+    }
+
+    /// Next `n` bits (at most [`MAX_PEEK_BITS`]) without moving, zero past the end of the data.
+    /// Bounds are only checked in debug builds, which is what the Huffman decoder relies on to
+    /// look ahead at the end of a granule.
+    #[inline]
+    pub fn peek_bits_unchecked(&mut self, n: u32) -> u64 {
+        debug_assert!(n <= MAX_PEEK_BITS);
+        if n == 0 {
+            return 0;
         }
 
-        if n > 32 {
+        // `pos` is public and can move anywhere, so the cache is checked against it.
+        if self.pos < self.cache_start || self.pos + n as usize > self.cache_start + 64 {
+            self.refill();
+        }
+
+        (self.cache << (self.pos - self.cache_start)) >> (64 - n)
+    }
+
+    #[inline]
+    pub fn skip_bits_unchecked(&mut self, n: usize) {
+        debug_assert!(self.pos + n <= self.total_bits);
+        self.pos += n;
+    }
+
+    /// Next `n` bits (at most [`MAX_PEEK_BITS`]) without moving.
+    pub fn peek_bits(&mut self, n: u32) -> Result<u64, ErrorType> {
+        if self.pos + n as usize > self.total_bits {
+            return Err(ErrorType::OutOfIndex);
+        }
+        if n > MAX_PEEK_BITS {
             return Err(ErrorType::Overflow);
         }
 
-        if n == 0 {
-            return Ok(0);
+        Ok(self.peek_bits_unchecked(n))
+    }
+
+    pub fn skip_bits(&mut self, n: usize) -> Result<(), ErrorType> {
+        if self.pos + n > self.total_bits {
+            return Err(ErrorType::OutOfIndex);
         }
 
-        let start_byte_index = self.pos / 8;
-        let end_byte_index = (self.pos + n as usize - 1) / 8;
+        self.pos += n;
+        Ok(())
+    }
 
-        let start_offset: u32 = ((0xff >> (self.pos as u8 % 8)) as u32) << (((n - 1) / 8) * 8);
-        let end_offset: u32 = 0xff << (7 - ((self.pos as u32 + n - 1) as u8 % 8));
+    pub fn read_bit(&mut self) -> Result<bool, ErrorType> {
+        let bit = self.peek_bits(1)? == 1;
+        self.pos += 1;
 
-        let mask: u32 = if start_byte_index == end_byte_index {
-            (start_offset) & (end_offset)
-        } else {
-            (start_offset) | (end_offset)
-        };
+        Ok(bit)
+    }
 
-        let mut result: u32 = 0;
+    /// Reads up to [`MAX_PEEK_BITS`] bits.
+    pub fn read_bits(&mut self, n: u32) -> Result<u64, ErrorType> {
+        let bits = self.peek_bits(n)?;
+        self.pos += n as usize;
 
-        for i in 0..=(n as usize - 1) / 8 {
-            result |= (self.data[i + start_byte_index] as u32) << (((n as usize - 1) / 8 - i) * 8)
+        Ok(bits)
+    }
+
+    pub fn get_bits(&mut self, n: u32) -> Result<u32, ErrorType> {
+        if self.pos + n as usize > self.total_bits {
+            return Err(ErrorType::OutOfIndex);
         }
 
-        result = (result & mask) >> (7 - ((self.pos as u32 + n - 1) as u8 % 8));
+        if n > 32 {
+            return Err(ErrorType::Overflow);
+        }
 
-        self.move_pos(n as isize)?;
+        let bits = self.peek_bits_unchecked(n) as u32;
+        self.pos += n as usize;
 
-        Ok(result)
+        Ok(bits)
     }
 
     pub fn set_pos(&mut self, pos: usize) -> Result<(), ErrorType> {
@@ -81,51 +273,189 @@ impl Buffer {
         Err(ErrorType::OutOfIndex)
     }
 
-    pub fn set_pos_next_frame(&mut self) -> Result<(), ErrorType> {
-        let mut bits = self.get_bits(15)?;
-        loop {
-            bits &= 0x7fff;
-            bits |= self.get_bits(1)? & 0b1;
-
-            if bits == 0x7ffd {
-                self.move_pos(-15)?;
-                break Ok(());
+    /// Moves to the next byte-aligned Layer III frame header at or after the current position and
+    /// returns its byte offset. A header has 11 set sync bits, a version other than the reserved
+    /// `01`, layer bits `01`, and neither the reserved bitrate nor the reserved sample rate.
+    ///
+    /// Candidates are found with `memchr` on the `0xff` sync byte, so stretches of junk are
+    /// skipped a word at a time rather than bit by bit.
+    pub fn set_pos_next_frame(&mut self) -> Result<usize, ErrorType> {
+        let mut offset = self.pos.div_ceil(8);
+
+        while let Some(found) = self
+            .data
+            .get(offset..)
+            .and_then(|data| memchr::memchr(0xff, data))
+        {
+            let candidate = offset + found;
+            match self.data.get(candidate + 1..candidate + 4) {
+                Some(&[b1, b2, _]) if is_header_start(b1, b2) => {
+                    self.pos = candidate * 8;
+                    return Ok(candidate);
+                }
+                Some(_) => offset = candidate + 1,
+                None => break,
             }
-            bits <<= 1;
         }
+
+        Err(ErrorType::OutOfIndex)
+    }
+
+    /// Length of the frame starting with `header`. For free format streams it is the distance to
+    /// the next frame with the same version, layer and sample rate, measured once.
+    pub fn frame_length(&mut self, header: &Header) -> Result<usize, ErrorType> {
+        match header.frame_length() {
+            Err(ErrorType::FreeFormat) => {}
+            length => return length,
+        }
+
+        let padding = header.padding_bit as usize * header.slot_length();
+        if let Some(length) = self.free_format_length {
+            return Ok(length + padding);
+        }
+
+        let offset = header.offset_byte();
+        let (second, third) = (self.data[offset + 1], self.data[offset + 2] & 0xfc);
+        let search_end = self.data.len().min(offset + MAX_FREE_FORMAT_LENGTH + 3);
+        let next = self.data[offset + 4..search_end]
+            .windows(3)
+            .position(|bytes| bytes[0] == 0xff && bytes[1] == second && bytes[2] & 0xfc == third)
+            .ok_or(ErrorType::FreeFormat)?;
+
+        let length = 4 + next - padding;
+        self.free_format_length = Some(length);
+
+        Ok(length + padding)
     }
 
-    pub fn extract_frames(&mut self) -> Vec<Frame> {
-        let mut frames: Vec<Frame> = Vec::new();
+    pub fn frame_data(&self, frame: &Frame) -> &[u8] {
+        &self.data[frame.offset_byte()..frame.offset_byte() + frame.length_byte()]
+    }
 
+    /// Next complete frame at or after the current position, leaving the position at its end.
+    /// Stops at the end of the data or at a truncated frame.
+    pub fn next_frame(&mut self) -> Option<Frame> {
         loop {
-            if let Err(err) = self.set_pos_next_frame() {
-                match err {
-                    ErrorType::OutOfIndex => break,
-                    _ => continue,
+            let offset = self.set_pos_next_frame().ok()?;
+
+            let frame = match Frame::create_from_buffer(self) {
+                Ok(frame) if frame.header().validate_header().is_ok() => frame,
+                _ => {
+                    // False sync, keep searching right after it.
+                    self.pos = (offset + 1) * 8;
+                    continue;
                 }
+            };
+
+            // A frame running past the end of the data is truncated.
+            let end = frame.offset_byte() + frame.length_byte();
+            if end > self.data.len() {
+                return None;
             }
 
-            let pos = self.pos;
-            let frame = Frame::create_from_buffer(self);
+            self.pos = end * 8;
+            return Some(frame);
+        }
+    }
 
-            if frame.is_err() {
-                continue;
-            }
+    /// Skips an ID3v2 tag when the position is in front of the audio.
+    #[cfg(feature = "alloc")]
+    fn skip_tag(&mut self) -> Result<(), ErrorType> {
+        let audio_start = tag::id3v2_length(&self.data) * 8;
+        if self.pos < audio_start {
+            self.set_pos(audio_start)?;
+        }
 
-            let pos = self.pos;
-            if frame.as_ref().unwrap().header().validate_header().is_err() {
+        Ok(())
+    }
+
+    /// Next complete frame like [`Buffer::next_frame`], reading only its 4-byte header and
+    /// jumping over the rest. Side info is not checked, so frames whose side info is corrupt
+    /// are included.
+    pub fn next_record(&mut self) -> Option<FrameRecord> {
+        while let Ok(offset) = self.set_pos_next_frame() {
+            let length = Header::create_from_buffer(self).and_then(|header| {
+                header.validate_header()?;
+                Ok((self.frame_length(&header)?, header))
+            });
+            let Ok((length, header)) = length else {
+                self.pos = (offset + 1) * 8;
                 continue;
+            };
+
+            let end = offset + length;
+            if end > self.data.len() {
+                return None;
             }
 
-            frames.push(frame.unwrap());
+            self.pos = end * 8;
+            return Some(FrameRecord::create_from_header(&header, offset, length));
+        }
+
+        None
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn extract_frames(&mut self) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = Vec::new();
+        if self.skip_tag().is_err() {
+            return frames;
+        }
+
+        while let Some(frame) = self.next_frame() {
+            frames.push(frame);
         }
 
         frames
     }
+
+    /// Finds the frames like [`Buffer::extract_frames`], with [`Buffer::next_record`].
+    #[cfg(feature = "alloc")]
+    pub fn scan_headers(&mut self) -> Vec<FrameRecord> {
+        let mut records = Vec::new();
+        if self.skip_tag().is_err() {
+            return records;
+        }
+
+        while let Some(record) = self.next_record() {
+            records.push(record);
+        }
+
+        records
+    }
+
+    /// Finds the audio frames like [`Buffer::scan_headers`], leaving out a leading Xing/Info or
+    /// VBRI frame.
+    #[cfg(feature = "std")]
+    pub fn scan_audio_headers(&mut self) -> Vec<FrameRecord> {
+        let mut records = self.scan_headers();
+
+        if let Some(first) = records.first() {
+            self.pos = first.offset * 8;
+            let is_info = Header::create_from_buffer(self).is_ok_and(|header| {
+                let data = &self.data[first.offset..first.offset + first.length];
+                XingHeader::parse(data, &header).is_some() || VbriHeader::parse(data).is_some()
+            });
+            if is_info {
+                records.remove(0);
+            }
+        }
+
+        records
+    }
+}
+
+/// Overwrites the `n` bits (at most 32) of `data` starting at bit `pos` with `value`, most
+/// significant bit first, as [`Buffer::get_bits`] reads them.
+pub fn write_bits(data: &mut [u8], pos: usize, n: u32, value: u32) {
+    for i in 0..n as usize {
+        let bit = (value >> (n as usize - 1 - i)) & 1;
+        let (byte, shift) = ((pos + i) / 8, 7 - (pos + i) % 8);
+        data[byte] = data[byte] & !(1 << shift) | (bit as u8) << shift;
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -136,6 +466,76 @@ mod test {
         assert_eq!(buffer.data.len() / 1024, 100);
     }
 
+    #[test]
+    fn test_set_pos_next_frame_byte_aligned() {
+        let mut buffer = Buffer::create_buffer_from_data(vec![
+            0x7f, 0xfd, 0x90, 0x00, // sync one bit into the data
+            0xff, 0xfb, 0xf0, 0x00, // reserved bitrate
+            0xff, 0xfb, 0x90, 0x64, 0x00,
+        ]);
+
+        assert_eq!(buffer.set_pos_next_frame(), Ok(8));
+        assert_eq!(buffer.pos, 64);
+
+        buffer.pos = 65;
+        assert_eq!(buffer.set_pos_next_frame(), Err(ErrorType::OutOfIndex));
+    }
+
+    #[test]
+    fn test_scan_headers() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let records = buffer.scan_headers();
+
+        buffer.pos = 0;
+        let frames = buffer.extract_frames();
+
+        assert_eq!(records.len(), frames.len());
+        assert!(records.iter().zip(&frames).all(|(record, frame)| {
+            record.offset == frame.offset_byte() && record.length == frame.length_byte()
+        }));
+        assert_eq!(records[1].bitrate, 192);
+
+        // The Info frame, then 153 frames of 1152 samples at 44.1kHz.
+        buffer.pos = 0;
+        let records = buffer.scan_audio_headers();
+        assert_eq!(records.len(), 153);
+        assert_eq!(records[0].offset, frames[1].offset_byte());
+        assert!((crate::frame::duration(&records) - 3.997).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_mapped_buffer_matches_read_buffer() {
+        let mut read = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let mut mapped = Buffer::create_buffer_from_mapped_file("mp3-examples/test_data_100kb.mp3");
+
+        assert!(matches!(mapped.data, Data::Mapped(_)));
+        assert_eq!(*read.data, *mapped.data);
+
+        let frames = mapped.extract_frames();
+        assert_eq!(frames.len(), read.extract_frames().len());
+        assert_eq!(
+            mapped.frame_data(&frames[1]).as_ptr(),
+            mapped.data[frames[1].offset_byte()..].as_ptr()
+        );
+    }
+
+    #[test]
+    fn test_borrowed_buffer_next_record() {
+        let data = include_bytes!("../mp3-examples/test_data_100kb.mp3");
+        let mut buffer = Buffer::create_buffer_from_slice(data);
+
+        let mut count = 0;
+        while let Some(record) = buffer.next_record() {
+            assert_eq!(buffer.pos, (record.offset + record.length) * 8);
+            count += 1;
+        }
+        assert_eq!(count, 154);
+
+        let shared = buffer.share();
+        assert!(matches!(shared.data, Data::Borrowed(_)));
+        assert_eq!(shared.data.as_ptr(), data.as_ptr());
+    }
+
     #[test]
     fn test_get_bits() {
         let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
@@ -145,4 +545,39 @@ mod test {
         assert_eq!(buffer.get_bits(2).unwrap(), 1); // LAYER III
         assert_eq!(buffer.get_bits(1).unwrap(), 1); // Error Protection
     }
+
+    #[test]
+    fn test_bit_cache_reads() {
+        let mut buffer = Buffer::create_buffer_from_data(vec![0xff, 0xfb, 0x90, 0x64, 0x00]);
+
+        assert_eq!(buffer.peek_bits(12), Ok(0xfff));
+        assert_eq!(buffer.read_bits(40), Ok(0xfffb906400));
+        assert_eq!(buffer.get_bits(1), Err(ErrorType::OutOfIndex));
+
+        // A read ending exactly at the end of the data.
+        buffer.pos = 20;
+        assert_eq!(buffer.get_bits(20), Ok(0x06400));
+        assert_eq!(buffer.peek_bits_unchecked(8), 0);
+
+        buffer.pos = 11;
+        buffer.skip_bits(2).unwrap();
+        assert_eq!(buffer.read_bit(), Ok(false));
+        assert_eq!(buffer.read_bit(), Ok(true));
+    }
+
+    #[test]
+    fn test_free_format_frame_length() {
+        let buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+
+        // The Info frame is 417 bytes at 128kb/sec without padding.
+        let mut frame = buffer.data[..417].to_vec();
+        frame[2] &= 0x0f;
+
+        let mut buffer = Buffer::create_buffer_from_data(frame.repeat(3));
+        let frames = buffer.extract_frames();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(buffer.free_format_length, Some(417));
+        assert!(frames.iter().all(|frame| frame.length_byte() == 417));
+    }
 }
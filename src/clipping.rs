@@ -0,0 +1,186 @@
+use std::fmt::{self, Display};
+
+use crate::{decoder, loudness::Oversampler, stream::AudioStream};
+
+/// Largest decoded sample that still fits a 16-bit sample, as 32767/32768.
+const MAX_SAMPLE: f32 = 1.0 - 1.0 / 32768.0;
+
+/// A sample past full scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Over {
+    /// Decoded sample it lies at, or the one before for inter-sample overs, counted from the
+    /// first one.
+    pub sample: usize,
+    /// Time in seconds from the start of the decoded audio, after the encoder and decoder delay.
+    pub time: f64,
+    /// Its magnitude, above 1.
+    pub value: f32,
+}
+
+/// Peaks and overs of one channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelPeaks {
+    /// Largest magnitude of a decoded sample, in dBFS.
+    pub sample_peak: f64,
+    /// Largest magnitude of the signal between decoded samples as well, in dBTP.
+    pub true_peak: f64,
+    /// Decoded samples that do not fit a 16-bit sample, and clip on the way to the speakers.
+    pub clipped: Vec<Over>,
+    /// Interpolated samples past full scale between decoded samples that fit, which clip in
+    /// the digital to analogue converter or after resampling.
+    pub inter_sample: Vec<Over>,
+}
+
+impl ChannelPeaks {
+    pub fn is_clean(&self) -> bool {
+        self.clipped.is_empty() && self.inter_sample.is_empty()
+    }
+}
+
+impl Display for ChannelPeaks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sample peak {:.2} dBFS, true peak {:.2} dBTP, {} clipped sample(s), {} inter-sample over(s)",
+            self.sample_peak,
+            self.true_peak,
+            self.clipped.len(),
+            self.inter_sample.len()
+        )
+    }
+}
+
+/// Peaks of a channel as they are decoded.
+struct Scan {
+    oversampler: Oversampler,
+    sample_peak: f32,
+    true_peak: f32,
+    clipped: Vec<usize>,
+    peaks: ChannelPeaks,
+}
+
+impl Scan {
+    fn push(&mut self, index: usize, sample: f32, time: impl Fn(usize) -> f64) {
+        let magnitude = sample.abs();
+        self.sample_peak = self.sample_peak.max(magnitude);
+        // Down to -32768, one more than up.
+        if !(-1.0..=MAX_SAMPLE).contains(&sample) {
+            self.clipped.push(index);
+            self.peaks.clipped.push(Over {
+                sample: index,
+                time: time(index),
+                value: magnitude,
+            });
+        }
+
+        let interpolated = self.oversampler.push(sample);
+        self.true_peak = self.true_peak.max(interpolated).max(magnitude);
+        // The interpolated samples lie between the decoded samples `DELAY` and `DELAY - 1` back.
+        let Some(before) = index.checked_sub(Oversampler::DELAY) else {
+            return;
+        };
+        let next_to_clipped = self
+            .clipped
+            .iter()
+            .rev()
+            .take_while(|&&clipped| clipped >= before)
+            .any(|&clipped| clipped <= before + 1);
+        if interpolated > 1.0 && !next_to_clipped {
+            self.peaks.inter_sample.push(Over {
+                sample: before,
+                time: time(before),
+                value: interpolated,
+            });
+        }
+    }
+}
+
+/// Decodes `stream` and finds, in every channel, the sample and true peak, the samples that
+/// clip once converted to 16 bits, and the overs between samples that only show once the
+/// decoded signal is interpolated, as a digital to analogue converter does.
+pub fn clipping(stream: &AudioStream) -> Vec<ChannelPeaks> {
+    let header = stream.header();
+    let frequency = header.get_frequency().unwrap_or(44100) as f64;
+    let delay = stream.delay() as f64;
+    let time = |sample: usize| (sample as f64 - delay).max(0.0) / frequency;
+    let channels = stream.channel_count();
+
+    let mut scans: Vec<Scan> = (0..channels)
+        .map(|_| Scan {
+            oversampler: Oversampler::new(),
+            sample_peak: 0.0,
+            true_peak: 0.0,
+            clipped: Vec::new(),
+            peaks: ChannelPeaks::default(),
+        })
+        .collect();
+
+    let mut index = 0;
+    for (_, samples) in decoder::samples(stream) {
+        for (scan, samples) in scans.iter_mut().zip(&samples) {
+            for (i, &sample) in samples.iter().enumerate() {
+                scan.push(index + i, sample, time);
+            }
+        }
+        index += samples[0].len();
+    }
+
+    scans
+        .into_iter()
+        .map(|mut scan| {
+            // Let the interpolator run past the last sample.
+            for i in 0..Oversampler::DELAY {
+                scan.push(index + i, 0.0, time);
+            }
+            scan.peaks.sample_peak = 20.0 * (scan.sample_peak as f64).log10();
+            scan.peaks.true_peak = 20.0 * (scan.true_peak as f64).log10();
+            scan.peaks
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer::Buffer, gain, header::Mode};
+
+    #[test]
+    fn test_clipping() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let stream = AudioStream::create_from_buffer(buffer.share()).unwrap();
+        let channels = clipping(&stream);
+        assert_eq!(channels.len(), 2);
+
+        // Three decoded samples reach full scale, in frames 26, 73 and 152.
+        let clipped: Vec<&Over> = channels.iter().flat_map(|peaks| &peaks.clipped).collect();
+        assert_eq!(clipped.len(), 3);
+        let mut frames: Vec<usize> = clipped.iter().map(|over| over.sample / 1152).collect();
+        frames.sort();
+        assert_eq!(frames, [26, 73, 152]);
+        for peaks in &channels {
+            assert!(peaks.true_peak >= peaks.sample_peak);
+            assert!(peaks.clipped.iter().all(|over| over.value > MAX_SAMPLE));
+            assert!(peaks.inter_sample.iter().all(|over| over.value > 1.0));
+        }
+        assert!(channels.iter().any(|peaks| peaks.sample_peak >= 0.0));
+        // Some overs only show between the decoded samples.
+        assert!(channels.iter().any(|peaks| !peaks.inter_sample.is_empty()));
+
+        // Four steps down (6dB) leave nothing past full scale.
+        let change = gain::apply_gain(&mut buffer, -4).unwrap();
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(change.data)).unwrap();
+        for (peaks, before) in clipping(&stream).iter().zip(&channels) {
+            assert!(peaks.is_clean(), "{peaks}");
+            assert!((peaks.sample_peak - before.sample_peak + 6.02).abs() < 0.01);
+        }
+
+        // A stream starting with a mono frame still gets the peaks of both channels.
+        let mut data = std::fs::read("mp3-examples/test_data_100kb.mp3").unwrap();
+        data[3] |= 0xc0;
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(data)).unwrap();
+        assert_eq!(stream.header().mode, Mode::SingleChannel);
+        assert_eq!(clipping(&stream).len(), 2);
+    }
+}
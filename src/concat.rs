@@ -0,0 +1,120 @@
+use crate::{
+    error::ErrorType,
+    header::{Header, Mode},
+    stream::AudioStream,
+    xing::{LameHeader, XingHeader, MAX_LAME_DELAY},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Compatibility {
+    Compatible,
+    /// Same channel count, but a different stereo coding (e.g. Stereo and Joint Stereo).
+    StereoModeDiffers,
+    Incompatible,
+}
+
+impl Compatibility {
+    pub fn check(first: &Header, other: &Header) -> Self {
+        if first.version != other.version
+            || first.layer != other.layer
+            || first.frequency != other.frequency
+            || (first.mode == Mode::SingleChannel) != (other.mode == Mode::SingleChannel)
+        {
+            return Compatibility::Incompatible;
+        }
+
+        if first.mode != other.mode {
+            return Compatibility::StereoModeDiffers;
+        }
+
+        Compatibility::Compatible
+    }
+}
+
+/// A joined stream.
+pub struct Joined {
+    pub data: Vec<u8>,
+    /// Whether the summed encoder delay or padding went past the 4095 samples the LAME header
+    /// holds and was cut to it, so gapless players trim the wrong number of samples.
+    pub gapless_capped: bool,
+}
+
+/// Joins the audio frames of `inputs` behind a single Xing/Info frame.
+///
+/// The LAME fields are taken from the first input that has them, with the encoder delays and
+/// paddings of all inputs summed, up to the 4095 samples the fields hold.
+pub fn concat(inputs: &[AudioStream]) -> Result<Joined, ErrorType> {
+    let first = inputs.first().ok_or(ErrorType::NoFrames)?;
+
+    if inputs.iter().any(|input| {
+        Compatibility::check(first.header(), input.header()) == Compatibility::Incompatible
+    }) {
+        return Err(ErrorType::IncompatibleStreams);
+    }
+
+    let mut base = inputs.iter().find_map(AudioStream::xing).cloned();
+    let mut gapless_capped = false;
+    if let Some(lame) = base.as_mut().and_then(|base| base.lame.as_mut()) {
+        let mut sum = |field: fn(&LameHeader) -> u16| {
+            let sum: usize = inputs
+                .iter()
+                .filter_map(|input| input.lame().map(|lame| field(lame) as usize))
+                .sum();
+            gapless_capped |= sum > MAX_LAME_DELAY;
+            sum.min(MAX_LAME_DELAY) as u16
+        };
+        lame.encoder_delay = sum(|lame| lame.encoder_delay);
+        lame.encoder_padding = sum(|lame| lame.encoder_padding);
+    }
+
+    let audio: Vec<&[u8]> = inputs
+        .iter()
+        .flat_map(|input| input.frames().iter().map(|frame| input.frame_data(frame)))
+        .collect();
+
+    Ok(Joined {
+        data: XingHeader::build_stream(&audio, first.header(), base.as_ref())?,
+        gapless_capped,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_concat_same_file() {
+        let input = || {
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+                "mp3-examples/test_data_100kb.mp3",
+            ))
+            .unwrap()
+        };
+        let inputs = [input(), input()];
+
+        let joined = concat(&inputs).unwrap();
+        assert!(!joined.gapless_capped);
+        let mut buffer = Buffer::create_buffer_from_data(joined.data);
+        let frames = buffer.extract_frames();
+        let xing = XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+
+        assert_eq!(frames.len(), 1 + 2 * 153);
+        assert_eq!(xing.frames, Some(2 * 153));
+        assert_eq!(xing.bytes, Some(buffer.data.len() as u32));
+        let lame = xing.lame.unwrap();
+        assert_eq!(
+            (lame.encoder_delay, lame.encoder_padding),
+            (2 * 576, 2 * 679)
+        );
+
+        // Eight files of 576 samples of delay need more than the LAME header holds.
+        let inputs: Vec<AudioStream> = (0..8).map(|_| input()).collect();
+        let joined = concat(&inputs).unwrap();
+        assert!(joined.gapless_capped);
+        let mut buffer = Buffer::create_buffer_from_data(joined.data);
+        let frames = buffer.extract_frames();
+        let xing = XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+        assert_eq!(xing.lame.unwrap().encoder_delay, MAX_LAME_DELAY as u16);
+    }
+}
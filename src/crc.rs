@@ -0,0 +1,47 @@
+/// CRC-16 (polynomial 0x8005, reflected, initial value 0) used by the LAME tag
+/// for both the music CRC and the tag CRC.
+pub fn crc16_lame(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, byte| {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// CRC-16 (polynomial 0x8005, initial value 0xffff) protecting the frame header and side info
+/// when `error_protection` is set.
+pub fn crc16_mpeg(data: &[u8]) -> u16 {
+    crc16_mpeg_update(0xffff, data)
+}
+
+/// Continues [`crc16_mpeg`] over more data.
+pub fn crc16_mpeg_update(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 == 0x8000 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crc16_check_values() {
+        assert_eq!(crc16_lame(b"123456789"), 0xbb3d);
+        assert_eq!(crc16_mpeg(b"123456789"), 0xaee7);
+    }
+}
@@ -0,0 +1,717 @@
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    frame::Frame,
+    header::{Header, Mode, Version},
+    huffman,
+    side_info::{ChannelInfo, SLEN},
+    stream::AudioStream,
+    synthesis::Synthesis,
+};
+
+/// Spectral lines of a granule and channel.
+pub const LINES: usize = 576;
+/// Lines of each of the three windows of a short block.
+pub const SHORT_LINES: usize = LINES / 3;
+
+/// Main data bytes kept from earlier frames, as far back as `main_data_begin` reaches.
+pub const MAX_RESERVOIR: usize = 511;
+
+// Widths of the scale factor bands, from minimp3 (CC0), by sample rate: 11.025/12kHz and 8kHz
+// (MPEG-2.5), 22.05, 24 and 16kHz (MPEG-2), 44.1, 48 and 32kHz (MPEG-1); see `band_row`. Short
+// and mixed blocks list every band three times, once per window, as the bitstream orders them.
+#[rustfmt::skip]
+const LONG_BANDS: [[u8; 22]; 8] = [
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [12, 12, 12, 12, 12, 12, 16, 20, 24, 28, 32, 40, 48, 56, 64, 76, 90, 2, 2, 2, 2, 2],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 18, 22, 26, 32, 38, 46, 54, 62, 70, 76, 36],
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 8, 10, 12, 16, 20, 24, 28, 34, 42, 50, 54, 76, 158],
+    [4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 10, 12, 16, 18, 22, 28, 34, 40, 46, 54, 54, 192],
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 10, 12, 16, 20, 24, 30, 38, 46, 56, 68, 84, 102, 26],
+];
+
+#[rustfmt::skip]
+const SHORT_BANDS: [[u8; 39]; 8] = [
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    [8, 8, 8, 8, 8, 8, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 24, 24, 24, 28, 28, 28, 36, 36, 36, 2, 2, 2, 2, 2, 2, 2, 2, 2, 26, 26, 26],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 14, 14, 14, 18, 18, 18, 26, 26, 26, 32, 32, 32, 42, 42, 42, 18, 18, 18],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 32, 32, 32, 44, 44, 44, 12, 12, 12],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 22, 22, 22, 30, 30, 30, 56, 56, 56],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 6, 6, 6, 10, 10, 10, 12, 12, 12, 14, 14, 14, 16, 16, 16, 20, 20, 20, 26, 26, 26, 66, 66, 66],
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 26, 26, 26, 34, 34, 34, 42, 42, 42, 12, 12, 12],
+];
+
+#[rustfmt::skip]
+const MIXED_BANDS: [&[u8]; 8] = [
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    &[12, 12, 12, 4, 4, 4, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 24, 24, 24, 28, 28, 28, 36, 36, 36, 2, 2, 2, 2, 2, 2, 2, 2, 2, 26, 26, 26],
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 14, 14, 14, 18, 18, 18, 26, 26, 26, 32, 32, 32, 42, 42, 42, 18, 18, 18],
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 32, 32, 32, 44, 44, 44, 12, 12, 12],
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 22, 22, 22, 30, 30, 30, 56, 56, 56],
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 6, 6, 6, 10, 10, 10, 12, 12, 12, 14, 14, 14, 16, 16, 16, 20, 20, 20, 26, 26, 26, 66, 66, 66],
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 26, 26, 26, 34, 34, 34, 42, 42, 42, 12, 12, 12],
+];
+
+/// Scale factors added to bands 11 to 20 when `preflag` is set.
+const PRETAB: [u8; 10] = [1, 1, 1, 1, 2, 2, 3, 3, 3, 2];
+
+/// Moduli splitting an MPEG-2/2.5 `scalefac_compress` into the sizes of its four groups of
+/// scale factors: three ranges of values, then three more for the right channel of intensity
+/// stereo.
+const LSF_MODULI: [[u8; 4]; 6] = [
+    [5, 5, 4, 4],
+    [5, 5, 4, 1],
+    [4, 3, 1, 1],
+    [5, 6, 6, 1],
+    [4, 4, 4, 1],
+    [4, 3, 1, 1],
+];
+
+/// Scale factors in each of the four groups, for long, mixed and short blocks: first for MPEG-1,
+/// then for each of the [`LSF_MODULI`].
+const PARTITIONS: [[[u8; 4]; 7]; 3] = [
+    [
+        [6, 5, 5, 5],
+        [6, 5, 5, 5],
+        [6, 5, 7, 3],
+        [11, 10, 0, 0],
+        [7, 7, 7, 0],
+        [6, 6, 6, 3],
+        [8, 8, 5, 0],
+    ],
+    [
+        [8, 9, 6, 12],
+        [6, 9, 9, 9],
+        [6, 9, 12, 6],
+        [15, 18, 0, 0],
+        [6, 15, 12, 0],
+        [6, 12, 9, 6],
+        [6, 18, 9, 0],
+    ],
+    [
+        [9, 9, 6, 12],
+        [9, 9, 9, 9],
+        [9, 9, 12, 6],
+        [18, 18, 0, 0],
+        [12, 12, 12, 0],
+        [12, 9, 9, 6],
+        [15, 12, 9, 0],
+    ],
+];
+
+/// Left and right factors of the MPEG-1 intensity stereo positions 0 to 6.
+const INTENSITY_PAN: [(f32, f32); 7] = [
+    (0.0, 1.0),
+    (0.211_324_87, 0.788_675_1),
+    (0.366_025_4, 0.633_974_6),
+    (0.5, 0.5),
+    (0.633_974_6, 0.366_025_4),
+    (0.788_675_1, 0.211_324_87),
+    (1.0, 0.0),
+];
+
+/// Row of the band tables for the sample rate of `header`.
+fn band_row(header: &Header) -> usize {
+    let frequency = header.frequency as usize;
+    match header.version {
+        Version::MPEG1 => 5 + frequency,
+        Version::MPEG2 => 2 + frequency,
+        Version::MPEG2_5 => frequency.saturating_sub(1),
+    }
+}
+
+/// The scale factor bands of a granule and channel.
+struct Bands {
+    widths: &'static [u8],
+    /// Bands of long blocks, which come first.
+    long: usize,
+    /// Bands of short blocks, counting each window.
+    short: usize,
+}
+
+impl Bands {
+    fn new(header: &Header, channel: &ChannelInfo) -> Self {
+        let row = band_row(header);
+        match (
+            channel.windows_switching && channel.block_type == 2,
+            channel.mixed_block_flag,
+        ) {
+            (false, _) => Self {
+                widths: &LONG_BANDS[row],
+                long: 22,
+                short: 0,
+            },
+            (true, false) => Self {
+                widths: &SHORT_BANDS[row],
+                long: 0,
+                short: 39,
+            },
+            (true, true) => Self {
+                widths: MIXED_BANDS[row],
+                long: MIXED_BANDS[row].len() - 30,
+                short: 30,
+            },
+        }
+    }
+
+    /// First line of the short blocks, [`LINES`] without any.
+    fn short_start(&self) -> usize {
+        if self.short == 0 {
+            return LINES;
+        }
+        self.widths[..self.long]
+            .iter()
+            .map(|&width| width as usize)
+            .sum()
+    }
+}
+
+/// The requantised spectral lines of one granule of one channel.
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+    /// Lines in frequency order. From `short_start` on, the three windows of every frequency
+    /// follow each other.
+    pub lines: [f32; LINES],
+    /// First line of the short blocks, [`LINES`] for long blocks only.
+    pub short_start: usize,
+}
+
+impl Spectrum {
+    pub fn has_short_blocks(&self) -> bool {
+        self.short_start < LINES
+    }
+
+    /// The lines of short window `window` (0 to 2) in frequency order. Long lines before
+    /// `short_start`, in mixed blocks, are shared by the three windows, three to a line by power.
+    pub fn short_window(&self, window: usize) -> [f32; SHORT_LINES] {
+        core::array::from_fn(|line| {
+            if 3 * line >= self.short_start {
+                self.lines[3 * line + window]
+            } else {
+                let power: f32 = self.lines[3 * line..3 * line + 3]
+                    .iter()
+                    .map(|value| value * value)
+                    .sum();
+                (power / 3.0).sqrt()
+            }
+        })
+    }
+}
+
+/// Reads the scale factors of a granule and channel as their raw values into `raw`, which holds
+/// those of the previous granule for the bands `scfsi` says are shared, and returns them with
+/// `preflag` and `subblock_gain` added, as exponents in steps of `scalefac_scale`.
+fn read_scalefactors(
+    buffer: &mut Buffer,
+    header: &Header,
+    channel: &ChannelInfo,
+    is_right: bool,
+    scfsi: u8,
+    bands: &Bands,
+    raw: &mut [u8; 40],
+) -> [u8; 40] {
+    let kind = match (bands.long, bands.short) {
+        (_, 0) => 0,
+        (0, _) => 2,
+        _ => 1,
+    };
+    let is_lsf = header.version != Version::MPEG1;
+
+    let (sizes, group) = if is_lsf {
+        // The right channel of intensity stereo codes positions, with a different split.
+        let intensity = header.intensity_stereo && is_right;
+        let mut compress = (channel.scalefac_compress >> intensity as u16) as usize;
+        let mut group = if intensity { 3 } else { 0 };
+        loop {
+            let moduli = LSF_MODULI[group];
+            let product: usize = moduli.iter().map(|&m| m as usize).product();
+            if compress < product || group % 3 == 2 {
+                let mut sizes = [0; 4];
+                let mut divisor = 1;
+                for i in (0..4).rev() {
+                    sizes[i] = (compress / divisor % moduli[i] as usize) as u8;
+                    divisor *= moduli[i] as usize;
+                }
+                break (sizes, group + 1);
+            }
+            compress -= product;
+            group += 1;
+        }
+    } else {
+        let (slen1, slen2) = SLEN[channel.scalefac_compress as usize & 15];
+        ([slen1, slen1, slen2, slen2], 0)
+    };
+
+    let mut scalefactors = [0; 40];
+    let mut start = 0;
+    for (i, (&count, &size)) in PARTITIONS[kind][group].iter().zip(&sizes).enumerate() {
+        let range = start..start + count as usize;
+        start = range.end;
+        if scfsi & (8 >> i) != 0 {
+            scalefactors[range.clone()].copy_from_slice(&raw[range]);
+            continue;
+        }
+        for k in range {
+            let value = buffer.peek_bits_unchecked(size as u32) as u8;
+            buffer.pos += size as usize;
+            scalefactors[k] = value;
+            // The largest value marks an illegal intensity position in MPEG-2/2.5.
+            raw[k] = if is_lsf && size > 0 && value == (1 << size) - 1 {
+                u8::MAX
+            } else {
+                value
+            };
+        }
+    }
+
+    let shift = 3 - (channel.scalefac_scale as u8 + 1);
+    if bands.short > 0 {
+        for band in (bands.long..bands.long + bands.short).step_by(3) {
+            for window in 0..3 {
+                scalefactors[band + window] += channel.subblock_gain[window] << shift;
+            }
+        }
+    } else if channel.preflag || (is_lsf && channel.scalefac_compress >= 500) {
+        for (scalefactor, pre) in scalefactors[11..].iter_mut().zip(PRETAB) {
+            *scalefactor += pre;
+        }
+    }
+
+    scalefactors
+}
+
+/// The quantised values of a granule and channel, ending at bit `end` of `buffer`.
+fn read_values(
+    buffer: &mut Buffer,
+    channel: &ChannelInfo,
+    bands: &Bands,
+    end: usize,
+) -> [i32; LINES] {
+    let mut values = [0; LINES];
+
+    // The big_values region is split into up to three regions, each with its own table.
+    let (count0, count1) = match (channel.windows_switching, bands.long) {
+        (false, _) => (channel.region_count[0], channel.region_count[1]),
+        (true, 0) => (8, 255),
+        (true, _) => (7, 255),
+    };
+    let widths = bands.widths.iter().map(|&width| width as usize);
+    let region1: usize = widths.clone().take(count0 as usize + 1).sum();
+    let region2: usize = region1
+        + widths
+            .skip(count0 as usize + 1)
+            .take(count1 as usize + 1)
+            .sum::<usize>();
+
+    let big_values = (channel.big_values as usize * 2).min(LINES);
+    let mut line = 0;
+    while line < big_values {
+        let table = match line {
+            _ if line < region1 => channel.table_select[0],
+            _ if line < region2 => channel.table_select[1],
+            _ => channel.table_select[2],
+        };
+        values[line..line + 2].copy_from_slice(&huffman::read_pair(buffer, table));
+        line += 2;
+    }
+
+    while line + 4 <= LINES && buffer.pos < end {
+        let Some(quad) = huffman::read_quad(buffer, channel.count1_table_select, end) else {
+            break;
+        };
+        values[line..line + 4].copy_from_slice(&quad);
+        line += 4;
+    }
+
+    values
+}
+
+/// Scales every value by `|value|^(4/3)` and the gain of its band.
+fn requantize(
+    values: &[i32; LINES],
+    channel: &ChannelInfo,
+    bands: &Bands,
+    scalefactors: &[u8; 40],
+) -> [f32; LINES] {
+    let mut lines = [0.0; LINES];
+    let shift = channel.scalefac_scale as i32 + 1;
+
+    let mut start = 0;
+    for (&width, &scalefactor) in bands.widths.iter().zip(scalefactors) {
+        let exponent = channel.global_gain as i32 - 210 - ((scalefactor as i32) << shift);
+        let gain = (exponent as f32 / 4.0).exp2();
+        for line in start..start + width as usize {
+            let value = values[line];
+            lines[line] =
+                (value.unsigned_abs() as f32).powf(4.0 / 3.0) * gain * value.signum() as f32;
+        }
+        start += width as usize;
+    }
+
+    lines
+}
+
+/// Last band of each window (of the only window for long blocks) with a non-zero line in the
+/// right channel, -1 for none.
+fn top_bands(right: &[f32; LINES], bands: &Bands) -> [i32; 3] {
+    let mut top = [-1; 3];
+    let mut start = 0;
+    for (i, &width) in bands.widths.iter().enumerate() {
+        if right[start..start + width as usize]
+            .iter()
+            .any(|&line| line != 0.0)
+        {
+            top[i % 3] = i as i32;
+        }
+        start += width as usize;
+    }
+
+    if bands.long > 0 {
+        top = [*top.iter().max().unwrap_or(&-1); 3];
+    }
+    top
+}
+
+/// Turns mid/side and intensity coded bands of both channels into left and right. `positions`
+/// are the raw scale factors of the right channel, which hold the intensity positions.
+fn stereo(
+    lines: &mut [[f32; LINES]; 2],
+    header: &Header,
+    bands: &Bands,
+    positions: &mut [u8; 40],
+    intensity_scale: u16,
+) {
+    let is_mpeg1 = header.version == Version::MPEG1;
+    let ms_stereo = header.ms_stereo;
+    let mut top = [i32::MAX; 3];
+
+    if header.intensity_stereo {
+        top = top_bands(&lines[1], bands);
+        // The highest bands have no scale factor and take their position from the band below.
+        let windows = if bands.short > 0 { 3 } else { 1 };
+        let count = bands.widths.len();
+        for (window, &top) in top[..windows].iter().enumerate() {
+            let highest = count - windows + window;
+            let below = highest - windows;
+            positions[highest] = if top >= below as i32 {
+                if is_mpeg1 {
+                    3
+                } else {
+                    0
+                }
+            } else {
+                positions[below]
+            };
+        }
+    }
+
+    let illegal = if is_mpeg1 { 7 } else { 64 };
+    let [left, right] = lines;
+    let mut start = 0;
+    for (i, &width) in bands.widths.iter().enumerate() {
+        let range = start..start + width as usize;
+        start = range.end;
+        let position = positions[i];
+
+        if i as i32 > top[i % 3] && position < illegal {
+            let (kl, kr) = if is_mpeg1 {
+                INTENSITY_PAN[position as usize]
+            } else {
+                let k = (-((((position as i32 + 1) >> 1) << intensity_scale) as f32) / 4.0).exp2();
+                if position & 1 == 1 {
+                    (k, 1.0)
+                } else {
+                    (1.0, k)
+                }
+            };
+            for line in range {
+                right[line] = left[line] * kr;
+                left[line] *= kl;
+            }
+        } else if ms_stereo {
+            for line in range {
+                let (mid, side) = (left[line], right[line]);
+                left[line] = (mid + side) * core::f32::consts::FRAC_1_SQRT_2;
+                right[line] = (mid - side) * core::f32::consts::FRAC_1_SQRT_2;
+            }
+        }
+    }
+}
+
+/// Puts the short block lines in frequency order, the three windows of every frequency next to
+/// each other; the bitstream has them window after window within every band.
+fn reorder(lines: &mut [f32; LINES], bands: &Bands) {
+    let mut start = bands.short_start();
+    let ordered = *lines;
+    for band in bands.widths[bands.long..].chunks(3) {
+        let width = band[0] as usize;
+        for line in 0..width {
+            for window in 0..3 {
+                lines[start + 3 * line + window] = ordered[start + window * width + line];
+            }
+        }
+        start += 3 * width;
+    }
+}
+
+/// Decodes the main data of Layer III frames, keeping the bytes that later frames refer back to
+/// through the bit reservoir.
+#[derive(Default)]
+pub struct Decoder {
+    reservoir: Vec<u8>,
+    synthesis: [Synthesis; 2],
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The spectral lines of every granule (the only one of MPEG-2/2.5 frames) and channel of
+    /// `frame`, whose bytes are `data`. Frames must come in stream order; `MissingMainData` is
+    /// returned when `main_data_begin` points back before the first frame decoded.
+    pub fn decode_spectra(
+        &mut self,
+        frame: &Frame,
+        data: &[u8],
+    ) -> Result<Vec<Vec<Spectrum>>, ErrorType> {
+        let main_data = data
+            .get(data.len().saturating_sub(frame.main_data_length())..)
+            .ok_or(ErrorType::OutOfIndex)?;
+        let begin = frame.side_info().main_data_begin as usize;
+
+        let available = self.reservoir.len();
+        self.reservoir.extend_from_slice(main_data);
+        let result = if begin > available {
+            Err(ErrorType::MissingMainData)
+        } else {
+            Ok(decode_main_data(
+                frame,
+                &self.reservoir[available - begin..],
+            ))
+        };
+
+        let excess = self.reservoir.len().saturating_sub(MAX_RESERVOIR);
+        self.reservoir.drain(..excess);
+
+        result
+    }
+
+    /// The samples of every channel of `frame`, at full scale ±1, 576 to a granule. As with
+    /// [`Decoder::decode_spectra`], frames must come in stream order: the filterbanks overlap
+    /// every granule with the one before.
+    pub fn decode_samples(
+        &mut self,
+        frame: &Frame,
+        data: &[u8],
+    ) -> Result<Vec<Vec<f32>>, ErrorType> {
+        let spectra = self.decode_spectra(frame, data)?;
+        let granules = frame.side_info().granules.iter().zip(&spectra);
+
+        let mut samples = vec![Vec::with_capacity(spectra.len() * LINES); spectra[0].len()];
+        for (granule, spectra) in granules {
+            for (ch, (channel, spectrum)) in granule.channels().iter().zip(spectra).enumerate() {
+                samples[ch].extend(self.synthesis[ch].process(spectrum, channel));
+            }
+        }
+        Ok(samples)
+    }
+}
+
+/// Decodes every frame of `stream` in order into the samples of each channel, next to the
+/// frame. Frames that cannot be decoded, for want of the bit reservoir bytes before the first
+/// one, give silence in as many channels as the frame has.
+pub fn samples<'a>(
+    stream: &'a AudioStream,
+) -> impl Iterator<Item = (&'a Frame, Vec<Vec<f32>>)> + 'a {
+    let mut decoder = Decoder::new();
+
+    stream.frames().iter().map(move |frame| {
+        let samples = decoder
+            .decode_samples(frame, stream.frame_data(frame))
+            .unwrap_or_else(|_| {
+                let header = frame.header();
+                vec![vec![0.0; header.samples_per_frame()]; header.channel_count()]
+            });
+        (frame, samples)
+    })
+}
+
+/// Decodes the granules of `frame` from its main data, which starts at `main_data`.
+fn decode_main_data(frame: &Frame, main_data: &[u8]) -> Vec<Vec<Spectrum>> {
+    let header = frame.header();
+    let side_info = frame.side_info();
+    let granule_count = header.granule_count();
+    let joint_stereo = header.mode == Mode::JointStereo;
+
+    let mut buffer = Buffer::create_buffer_from_slice(main_data);
+    let mut raw = [[0; 40]; 2];
+    let mut spectra = Vec::with_capacity(granule_count);
+
+    for (gr, granule) in side_info.granules[..granule_count].iter().enumerate() {
+        let channels = granule.channels();
+        let mut lines = [[0.0; LINES]; 2];
+
+        for (ch, channel) in channels.iter().enumerate() {
+            let end = buffer.pos + channel.part_23_length as usize;
+            let bands = Bands::new(header, channel);
+            let scfsi = if gr == 1 && bands.short == 0 {
+                side_info.scfsi >> (4 * (channels.len() - 1 - ch)) & 15
+            } else {
+                0
+            };
+
+            let scalefactors = read_scalefactors(
+                &mut buffer,
+                header,
+                channel,
+                ch == 1,
+                scfsi,
+                &bands,
+                &mut raw[ch],
+            );
+            let values = read_values(&mut buffer, channel, &bands, end);
+            lines[ch] = requantize(&values, channel, &bands, &scalefactors);
+            buffer.pos = end;
+        }
+
+        if channels.len() == 2 && joint_stereo && (header.ms_stereo || header.intensity_stereo) {
+            let bands = Bands::new(header, &channels[0]);
+            stereo(
+                &mut lines,
+                header,
+                &bands,
+                &mut raw[1],
+                channels[1].scalefac_compress & 1,
+            );
+        }
+
+        spectra.push(
+            channels
+                .iter()
+                .zip(&mut lines)
+                .map(|(channel, lines)| {
+                    let bands = Bands::new(header, channel);
+                    reorder(lines, &bands);
+                    Spectrum {
+                        lines: *lines,
+                        short_start: bands.short_start(),
+                    }
+                })
+                .collect(),
+        );
+    }
+
+    spectra
+}
+
+/// Bits of the scale factors (part 2 of `part_23_length`) of every granule and channel of
+/// `frame`, read from its main data, which starts at `main_data`. The Huffman coded values
+/// follow them.
+pub(crate) fn part2_lengths(frame: &Frame, main_data: &[u8]) -> [[usize; 2]; 2] {
+    let header = frame.header();
+    let side_info = frame.side_info();
+    let mut buffer = Buffer::create_buffer_from_slice(main_data);
+    let mut raw = [[0; 40]; 2];
+    let mut lengths = [[0; 2]; 2];
+
+    for (gr, granule) in side_info.granules.iter().enumerate() {
+        let channels = granule.channels();
+        for (ch, channel) in channels.iter().enumerate() {
+            let start = buffer.pos;
+            let end = start + channel.part_23_length as usize;
+            let bands = Bands::new(header, channel);
+            let scfsi = if gr == 1 && bands.short == 0 {
+                side_info.scfsi >> (4 * (channels.len() - 1 - ch)) & 15
+            } else {
+                0
+            };
+
+            read_scalefactors(
+                &mut buffer,
+                header,
+                channel,
+                ch == 1,
+                scfsi,
+                &bands,
+                &mut raw[ch],
+            );
+            lengths[gr][ch] = (buffer.pos - start).min(end - start);
+            buffer.pos = end;
+        }
+    }
+
+    lengths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{frame, stream::AudioStream};
+
+    #[test]
+    fn test_decode_spectra() {
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+        let frames = stream.frames();
+        let mut decoder = Decoder::new();
+        let mut decoded = Vec::new();
+
+        for frame in frames {
+            let spectra = decoder
+                .decode_spectra(frame, stream.frame_data(frame))
+                .unwrap();
+            assert_eq!(spectra.len(), 2);
+
+            for (granule, spectra) in frame.side_info().granules.iter().zip(&spectra) {
+                for (channel, spectrum) in granule.channels().iter().zip(spectra) {
+                    assert_eq!(
+                        spectrum.has_short_blocks(),
+                        channel.windows_switching && channel.block_type == 2
+                    );
+                    // The encoder's lowpass, just under 19.3kHz.
+                    assert!(spectrum.lines[504..].iter().all(|&line| line == 0.0));
+                }
+            }
+            decoded.push(spectra);
+        }
+
+        let loudest = decoded
+            .iter()
+            .flatten()
+            .flatten()
+            .flat_map(|spectrum| spectrum.lines)
+            .fold(0.0, |max: f32, line| max.max(line.abs()));
+        assert!(loudest > 0.1 && loudest < 2.0, "{loudest}");
+
+        // A frame using the bit reservoir needs the main data of the frames before it.
+        let index = frames
+            .iter()
+            .position(|frame| frame.side_info().main_data_begin > 0)
+            .unwrap();
+        let frame = &frames[index];
+        assert_eq!(
+            Decoder::new()
+                .decode_spectra(frame, stream.frame_data(frame))
+                .unwrap_err(),
+            ErrorType::MissingMainData
+        );
+
+        let mut decoder = Decoder::new();
+        for frame in &frames[index - frame::priming_frames(frames, index)..index] {
+            decoder
+                .decode_spectra(frame, stream.frame_data(frame))
+                .unwrap();
+        }
+        let spectra = decoder
+            .decode_spectra(frame, stream.frame_data(frame))
+            .unwrap();
+        assert_eq!(spectra[1][1].lines, decoded[index][1][1].lines);
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::{frame::Frame, side_info::ChannelInfo, stream::AudioStream};
+
+/// Frames searched ahead of a mismatch for the point where both files are the same again.
+const RESYNC_WINDOW: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    Field {
+        name: String,
+        left: String,
+        right: String,
+    },
+    /// Bytes after the side info that differ, counting the extra bytes of the longer frame.
+    Payload { differing: usize },
+}
+
+/// A difference between the audio frames of two files, by frame index in each.
+#[derive(Debug, PartialEq)]
+pub enum FrameDiff {
+    /// Frame of the left file without a counterpart in the right one.
+    Removed { left: usize },
+    /// Frame of the right file without a counterpart in the left one.
+    Inserted { right: usize },
+    Changed {
+        left: usize,
+        right: usize,
+        differences: Vec<Difference>,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Field { name, left, right } => write!(f, "{name}: {left} -> {right}"),
+            Self::Payload { differing } => write!(f, "payload: {differing} bytes differ"),
+        }
+    }
+}
+
+fn compare<T: PartialEq + Display>(
+    differences: &mut Vec<Difference>,
+    name: impl Into<String>,
+    left: T,
+    right: T,
+) {
+    if left != right {
+        differences.push(Difference::Field {
+            name: name.into(),
+            left: left.to_string(),
+            right: right.to_string(),
+        });
+    }
+}
+
+/// Compares the named fields of `left` and `right`, naming them after `prefix`.
+macro_rules! compare_fields {
+    ($d:expr, $prefix:expr, $left:expr, $right:expr, $($field:ident),+) => {
+        $(compare($d, format!("{}{}", $prefix, stringify!($field)), $left.$field, $right.$field);)+
+    };
+}
+
+fn compare_channels(
+    d: &mut Vec<Difference>,
+    prefix: &str,
+    left: &ChannelInfo,
+    right: &ChannelInfo,
+) {
+    compare_fields!(
+        d,
+        prefix,
+        left,
+        right,
+        part_23_length,
+        big_values,
+        global_gain,
+        scalefac_compress,
+        windows_switching,
+        block_type,
+        mixed_block_flag,
+        preflag,
+        scalefac_scale,
+        count1_table_select
+    );
+    for i in 0..3 {
+        compare(
+            d,
+            format!("{prefix}table_select[{i}]"),
+            left.table_select[i],
+            right.table_select[i],
+        );
+        compare(
+            d,
+            format!("{prefix}subblock_gain[{i}]"),
+            left.subblock_gain[i],
+            right.subblock_gain[i],
+        );
+        compare(
+            d,
+            format!("{prefix}region_count[{i}]"),
+            left.region_count[i],
+            right.region_count[i],
+        );
+    }
+}
+
+/// Fields of the header and side info that differ between two frames, and whether their
+/// payload does.
+pub fn compare_frames(
+    left: &Frame,
+    left_data: &[u8],
+    right: &Frame,
+    right_data: &[u8],
+) -> Vec<Difference> {
+    let mut d = Vec::new();
+
+    let (lh, rh) = (left.header(), right.header());
+    compare_fields!(
+        &mut d,
+        "",
+        lh,
+        rh,
+        version,
+        layer,
+        error_protection,
+        padding_bit,
+        private_bit,
+        mode,
+        intensity_stereo,
+        ms_stereo,
+        copy_right,
+        copy_of_original,
+        emphasis
+    );
+    compare(
+        &mut d,
+        "bitrate",
+        lh.get_bitrate().unwrap_or(0),
+        rh.get_bitrate().unwrap_or(0),
+    );
+    compare(
+        &mut d,
+        "frequency",
+        lh.get_frequency().unwrap_or(0),
+        rh.get_frequency().unwrap_or(0),
+    );
+    if let (Some(lc), Some(rc)) = (left.crc(), right.crc()) {
+        compare(&mut d, "crc", format!("{lc:#06x}"), format!("{rc:#06x}"));
+    }
+
+    let (ls, rs) = (left.side_info(), right.side_info());
+    compare_fields!(&mut d, "", ls, rs, main_data_begin, private_bits, scfsi);
+    for (g, (lg, rg)) in ls.granules.iter().zip(&rs.granules).enumerate() {
+        for (ch, (lc, rc)) in lg.channels().iter().zip(rg.channels()).enumerate() {
+            compare_channels(&mut d, &format!("granule {g} channel {ch} "), lc, rc);
+        }
+    }
+
+    let left_main = &left_data[left_data.len() - left.main_data_length()..];
+    let right_main = &right_data[right_data.len() - right.main_data_length()..];
+    let differing = left_main
+        .iter()
+        .zip(right_main)
+        .filter(|(l, r)| l != r)
+        .count()
+        + left_main.len().abs_diff(right_main.len());
+    if differing > 0 {
+        d.push(Difference::Payload { differing });
+    }
+
+    d
+}
+
+fn frame_hashes(stream: &AudioStream) -> Vec<u64> {
+    stream
+        .frames()
+        .iter()
+        .map(|frame| {
+            let mut hasher = DefaultHasher::new();
+            stream.frame_data(frame).hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Differences between the audio frames of two files. Tags, junk and the Xing/Info frame are
+/// left out, so frames are matched by their bytes wherever they are in the files.
+///
+/// Identical frames are paired in order. At a mismatch the next frames of both files are searched
+/// for the closest pair of identical frames (within [`RESYNC_WINDOW`] frames); the frames before
+/// it are paired as changed, and the ones left over on one side are removed or inserted.
+pub fn diff(left: &AudioStream, right: &AudioStream) -> Vec<FrameDiff> {
+    let (lf, rf) = (left.frames(), right.frames());
+    let (lh, rh) = (frame_hashes(left), frame_hashes(right));
+    let same =
+        |i: usize, j: usize| lh[i] == rh[j] && left.frame_data(&lf[i]) == right.frame_data(&rf[j]);
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < lf.len() || j < rf.len() {
+        if i < lf.len() && j < rf.len() && same(i, j) {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        // Closest pair of identical frames ahead, by the number of frames skipped.
+        let resync = (1..=2 * RESYNC_WINDOW).find_map(|skipped| {
+            (0..=skipped)
+                .filter(|&k| k <= RESYNC_WINDOW && skipped - k <= RESYNC_WINDOW)
+                .map(|k| (i + k, j + skipped - k))
+                .find(|&(a, b)| a < lf.len() && b < rf.len() && same(a, b))
+        });
+        let (end_left, end_right) = match resync {
+            Some(pair) => pair,
+            // Nothing matches nearby: the current frames changed, or one file ended.
+            None => ((i + 1).min(lf.len()), (j + 1).min(rf.len())),
+        };
+
+        while i < end_left && j < end_right {
+            diffs.push(FrameDiff::Changed {
+                left: i,
+                right: j,
+                differences: compare_frames(
+                    &lf[i],
+                    left.frame_data(&lf[i]),
+                    &rf[j],
+                    right.frame_data(&rf[j]),
+                ),
+            });
+            i += 1;
+            j += 1;
+        }
+        diffs.extend((i..end_left).map(|left| FrameDiff::Removed { left }));
+        diffs.extend((j..end_right).map(|right| FrameDiff::Inserted { right }));
+        (i, j) = (end_left, end_right);
+    }
+
+    diffs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    const SAMPLE: &str = "mp3-examples/test_data_100kb.mp3";
+
+    #[test]
+    fn test_diff() {
+        let data = Buffer::create_buffer_from_file(SAMPLE).data.to_vec();
+        let left =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(data.clone())).unwrap();
+        let frames = left.frames();
+        let (f10, f20, f30) = (&frames[10], &frames[20], &frames[30]);
+
+        // Junk in front, frame 10 dropped, frame 20 duplicated and a payload byte of frame 30
+        // and the global_gain of its first channel changed.
+        let mut edited = vec![0u8; 100];
+        edited.extend_from_slice(&data[..f10.offset_byte()]);
+        edited.extend_from_slice(&data[f10.offset_byte() + f10.length_byte()..f20.offset_byte()]);
+        edited.extend_from_slice(left.frame_data(f20));
+        let start = edited.len();
+        edited.extend_from_slice(&data[f20.offset_byte()..]);
+        let f30_start = start + f30.offset_byte() - f20.offset_byte();
+        edited[f30_start + f30.length_byte() - 1] ^= 0xff;
+        // global_gain follows main_data_begin (9), private_bits (3), scfsi (8), part_23_length
+        // (12) and big_values (9): bits 41..49 of the side info.
+        edited[f30_start + 4 + 5] ^= 0x40;
+
+        let right =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(edited)).unwrap();
+        let diffs = diff(&left, &right);
+
+        assert_eq!(diffs.len(), 3, "{diffs:?}");
+        assert_eq!(diffs[0], FrameDiff::Removed { left: 10 });
+        assert_eq!(diffs[1], FrameDiff::Inserted { right: 20 });
+
+        let FrameDiff::Changed {
+            left: 30,
+            right: 30,
+            differences,
+        } = &diffs[2]
+        else {
+            panic!("frame 30 should differ: {:?}", diffs[2]);
+        };
+        assert_eq!(differences.len(), 2);
+        assert!(matches!(&differences[0], Difference::Field { name, .. }
+            if name == "granule 0 channel 0 global_gain"));
+        assert_eq!(differences[1], Difference::Payload { differing: 1 });
+
+        assert!(diff(&left, &left).is_empty());
+    }
+}
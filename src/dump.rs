@@ -0,0 +1,334 @@
+use std::io::{self, Write};
+
+use crate::{
+    buffer::Buffer,
+    crc,
+    error::ErrorType,
+    header::{Header, Mode, Version},
+    side_info::SLEN,
+};
+
+/// Colours cycled through to tell neighbouring fields apart.
+const COLORS: [&str; 6] = [
+    "\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+];
+const RESET: &str = "\x1b[0m";
+
+/// A field of the header, CRC or side info of a frame.
+#[derive(Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    /// Bit offset from the start of the frame.
+    pub start: usize,
+    pub length: usize,
+    pub value: u32,
+    /// What the value stands for, empty when it is just a number.
+    pub meaning: String,
+}
+
+/// Reads fields one after the other.
+struct FieldReader<'a> {
+    buffer: Buffer<'a>,
+    fields: Vec<Field>,
+}
+
+impl FieldReader<'_> {
+    fn read(
+        &mut self,
+        name: impl Into<String>,
+        length: u32,
+        meaning: impl FnOnce(u32) -> String,
+    ) -> Result<u32, ErrorType> {
+        let start = self.buffer.pos;
+        let value = self.buffer.get_bits(length)?;
+
+        self.fields.push(Field {
+            name: name.into(),
+            start,
+            length: length as usize,
+            value,
+            meaning: meaning(value),
+        });
+
+        Ok(value)
+    }
+}
+
+fn on_off(value: u32) -> String {
+    if value == 1 { "on" } else { "off" }.to_string()
+}
+
+fn none(_: u32) -> String {
+    String::new()
+}
+
+/// Every field of the header, CRC and side info of the frame in `data`, in bitstream order.
+pub fn frame_fields(data: &[u8]) -> Result<Vec<Field>, ErrorType> {
+    let header = Header::create_from_buffer(&mut Buffer::create_buffer_from_slice(data))?;
+    let mut r = FieldReader {
+        buffer: Buffer::create_buffer_from_slice(data),
+        fields: Vec::new(),
+    };
+
+    r.read("sync", 11, none)?;
+    r.read("version", 2, |_| header.version.to_string())?;
+    r.read("layer", 2, |_| header.layer.to_string())?;
+    r.read("protection_bit", 1, |value| {
+        if value == 0 { "CRC follows" } else { "no CRC" }.to_string()
+    })?;
+    r.read("bitrate_index", 4, |_| match header.get_bitrate() {
+        Ok(0) => "free format".to_string(),
+        Ok(bitrate) => format!("{bitrate}kb/sec"),
+        Err(_) => "reserved".to_string(),
+    })?;
+    r.read("sampling_frequency", 2, |_| {
+        header
+            .get_frequency()
+            .map_or("reserved".to_string(), |frequency| format!("{frequency}Hz"))
+    })?;
+    r.read("padding_bit", 1, |value| {
+        if value == 1 { "padded" } else { "not padded" }.to_string()
+    })?;
+    r.read("private_bit", 1, none)?;
+    r.read("mode", 2, |_| header.mode.to_string())?;
+    r.read("mode_extension", 2, |_| match header.mode {
+        Mode::JointStereo => format!(
+            "MS stereo {}, intensity stereo {}",
+            on_off(header.ms_stereo as u32),
+            on_off(header.intensity_stereo as u32)
+        ),
+        _ => "unused".to_string(),
+    })?;
+    r.read("copyright", 1, |value| {
+        if value == 1 {
+            "copyrighted"
+        } else {
+            "not copyrighted"
+        }
+        .to_string()
+    })?;
+    r.read("original", 1, |value| {
+        if value == 1 { "original" } else { "copy" }.to_string()
+    })?;
+    r.read("emphasis", 2, |value| {
+        ["none", "50/15 µs", "reserved", "CCITT J.17"][value as usize].to_string()
+    })?;
+
+    if header.error_protection {
+        let side_info = data
+            .get(6..6 + header.side_info_length())
+            .ok_or(ErrorType::OutOfIndex)?;
+        let computed = crc::crc16_mpeg_update(crc::crc16_mpeg(&data[2..4]), side_info);
+        r.read("crc", 16, |value| {
+            if value == computed as u32 {
+                "matches".to_string()
+            } else {
+                format!("mismatch, computed {computed:#06x}")
+            }
+        })?;
+    }
+
+    let is_mono = header.mode == Mode::SingleChannel;
+    let is_lsf = header.version != Version::MPEG1;
+    let channels = header.channel_count();
+
+    r.read("main_data_begin", if is_lsf { 8 } else { 9 }, |value| {
+        format!("{value} bytes back")
+    })?;
+    r.read(
+        "private_bits",
+        match (is_lsf, is_mono) {
+            (true, true) => 1,
+            (true, false) => 2,
+            (false, true) => 5,
+            (false, false) => 3,
+        },
+        none,
+    )?;
+    if !is_lsf {
+        for ch in 0..channels {
+            r.read(format!("scfsi[{ch}]"), 4, |value| {
+                let bands: Vec<&str> = ["0-5", "6-10", "11-15", "16-20"]
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| value & (8 >> i) != 0)
+                    .map(|(_, bands)| bands)
+                    .collect();
+                if bands.is_empty() {
+                    String::new()
+                } else {
+                    format!("bands {} from granule 0", bands.join(", "))
+                }
+            })?;
+        }
+    }
+
+    for gr in 0..header.granule_count() {
+        for ch in 0..channels {
+            let prefix = format!("granule {gr} channel {ch} ");
+            let name = |field: &str| format!("{prefix}{field}");
+
+            r.read(name("part_23_length"), 12, |value| format!("{value} bits"))?;
+            r.read(name("big_values"), 9, |value| {
+                format!("{} spectral lines", value * 2)
+            })?;
+            r.read(name("global_gain"), 8, |value| {
+                format!("{:+.1} dB", (value as f64 - 210.0) * 1.5)
+            })?;
+            r.read(
+                name("scalefac_compress"),
+                if is_lsf { 9 } else { 4 },
+                |value| {
+                    if is_lsf {
+                        return String::new();
+                    }
+                    let (slen1, slen2) = SLEN[value as usize];
+                    format!("slen1 {slen1}, slen2 {slen2}")
+                },
+            )?;
+            let windows_switching = r.read(name("windows_switching"), 1, on_off)? == 1;
+
+            if windows_switching {
+                r.read(name("block_type"), 2, |value| {
+                    ["reserved", "start", "short", "stop"][value as usize].to_string()
+                })?;
+                r.read(name("mixed_block_flag"), 1, on_off)?;
+                for i in 0..2 {
+                    r.read(name(&format!("table_select[{i}]")), 5, table_meaning)?;
+                }
+                for i in 0..3 {
+                    r.read(name(&format!("subblock_gain[{i}]")), 3, |value| {
+                        format!("x2^-{}", 2 * value)
+                    })?;
+                }
+            } else {
+                for i in 0..3 {
+                    r.read(name(&format!("table_select[{i}]")), 5, table_meaning)?;
+                }
+                r.read(name("region_count[0]"), 4, |value| {
+                    format!("{} scale factor bands", value + 1)
+                })?;
+                r.read(name("region_count[1]"), 3, |value| {
+                    format!("{} scale factor bands", value + 1)
+                })?;
+            }
+
+            if !is_lsf {
+                r.read(name("preflag"), 1, on_off)?;
+            }
+            r.read(name("scalefac_scale"), 1, |value| {
+                format!("step {}", if value == 1 { "1" } else { "1/2" })
+            })?;
+            r.read(name("count1_table_select"), 1, |value| {
+                format!("table {}", if value == 1 { "B" } else { "A" })
+            })?;
+        }
+    }
+
+    Ok(r.fields)
+}
+
+fn table_meaning(value: u32) -> String {
+    match value {
+        0 => "no table, all zero".to_string(),
+        4 | 14 => "unused table".to_string(),
+        table => format!("Huffman table {table}"),
+    }
+}
+
+/// Writes the bits of the header, CRC and side info of the frame in `data` with every field
+/// coloured (when `color` is set), a table of the fields, and the main data in hex. `offset` is
+/// the position of the frame in its file.
+pub fn write_dump(
+    out: &mut impl Write,
+    offset: usize,
+    data: &[u8],
+    fields: &[Field],
+    color: bool,
+) -> io::Result<()> {
+    let end = fields.last().map_or(0, |field| field.start + field.length);
+    let paint = |i: usize, text: &str| {
+        if color {
+            format!("{}{text}{RESET}", COLORS[i % COLORS.len()])
+        } else {
+            text.to_string()
+        }
+    };
+
+    // The bits, 32 to a row.
+    let mut field = 0;
+    for row in (0..end).step_by(32) {
+        write!(out, "{:#010x} ", offset + row / 8)?;
+        for bit in row..(row + 32).min(end) {
+            if bit % 8 == 0 {
+                write!(out, " ")?;
+            }
+            while fields[field].start + fields[field].length <= bit {
+                field += 1;
+            }
+            let value = (data[bit / 8] >> (7 - bit % 8)) & 1;
+            write!(out, "{}", paint(field, &value.to_string()))?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out)?;
+
+    for (i, field) in fields.iter().enumerate() {
+        let bits = format!("{:0width$b}", field.value, width = field.length);
+        writeln!(
+            out,
+            "{:>4}..{:<4} {:>3}.{} {} {:<12} {:<6} {}",
+            field.start,
+            field.start + field.length,
+            field.start / 8,
+            field.start % 8,
+            paint(i, &format!("{:<40}", field.name)),
+            bits,
+            field.value,
+            field.meaning
+        )?;
+    }
+
+    // The main data, which needs the Huffman decoder to make sense of.
+    let main_data = end.div_ceil(8);
+    writeln!(out, "\nmain data, {} bytes:", data.len() - main_data)?;
+    for (row, bytes) in data[main_data..].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        writeln!(
+            out,
+            "{:#010x}  {}",
+            offset + main_data + row * 16,
+            hex.join(" ")
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_fields() {
+        let buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        // The Info frame, which uses MS stereo.
+        let fields = frame_fields(&buffer.data).unwrap();
+
+        // 32 header bits and 256 bits of MPEG-1 stereo side info, without gaps.
+        let end = fields.iter().fold(0, |end, field| {
+            assert_eq!(field.start, end, "{}", field.name);
+            end + field.length
+        });
+        assert_eq!(end, 32 + 256);
+
+        let field = |name: &str| fields.iter().find(|field| field.name == name).unwrap();
+        assert_eq!(field("sync").value, 0x7ff);
+        assert_eq!(field("bitrate_index").meaning, "128kb/sec");
+        assert_eq!(
+            field("mode_extension").meaning,
+            "MS stereo on, intensity stereo off"
+        );
+        assert_eq!(field("granule 0 channel 0 part_23_length").start, 52);
+    }
+}
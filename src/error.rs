@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ErrorType {
     InvalidHeader,
     OutOfIndex,
@@ -6,8 +6,15 @@ pub enum ErrorType {
     UnknownLayer,
     UnknownVersion,
     UnknownBitrate,
+    FreeFormat,
     UnknownFrequency,
     UnknownMode,
     BigValuesOutOfRange,
     BlockTypeForbidden,
+    NoFrames,
+    IncompatibleStreams,
+    InvalidIndex,
+    MissingMainData,
+    MissingUndoTag,
+    ReservoirTooDeep,
 }
@@ -1,13 +1,56 @@
-use crate::{buffer::Buffer, error::ErrorType, side_info::SideInfo, Header};
+use crate::{
+    buffer::Buffer,
+    crc,
+    error::ErrorType,
+    header::{Header, Mode, Version},
+    side_info::SideInfo,
+};
 
 #[derive(Debug)]
 pub struct Frame {
     header: Header,
     crc: Option<u16>,
     side_info: SideInfo,
+    offset_byte: usize,
     length_byte: usize,
 }
 
+/// What the 4-byte header tells about a frame, as collected by [`Buffer::scan_headers`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRecord {
+    pub offset: usize,
+    pub length: usize,
+    pub version: Version,
+    /// In kb/sec, 0 for free format.
+    pub bitrate: u16,
+    pub frequency: u16,
+    pub mode: Mode,
+    pub samples: u16,
+}
+
+impl FrameRecord {
+    pub fn create_from_header(header: &Header, offset: usize, length: usize) -> Self {
+        Self {
+            offset,
+            length,
+            version: header.version,
+            bitrate: header.get_bitrate().unwrap_or(0),
+            frequency: header.get_frequency().unwrap_or(0),
+            mode: header.mode,
+            samples: header.samples_per_frame() as u16,
+        }
+    }
+}
+
+/// Total duration of `records` in seconds.
+pub fn duration(records: &[FrameRecord]) -> f64 {
+    records
+        .iter()
+        .filter(|record| record.frequency > 0)
+        .map(|record| record.samples as f64 / record.frequency as f64)
+        .sum()
+}
+
 impl Frame {
     pub fn header(&self) -> &Header {
         &self.header
@@ -21,27 +64,88 @@ impl Frame {
         self.crc
     }
 
+    pub fn offset_byte(&self) -> usize {
+        self.offset_byte
+    }
+
+    /// Moves the recorded position, for frames parsed from a window of a larger stream.
+    #[cfg(feature = "async")]
+    pub(crate) fn move_offset(&mut self, bytes: usize) {
+        self.offset_byte += bytes;
+        self.header.move_offset(bytes);
+    }
+
     pub fn length_byte(&self) -> usize {
         self.length_byte
     }
 
+    /// Bytes after the header, CRC and side info, which feed the bit reservoir.
+    pub fn main_data_length(&self) -> usize {
+        self.length_byte
+            .saturating_sub(4 + self.crc.map_or(0, |_| 2) + self.header.side_info_length())
+    }
+
+    /// CRC of the header and side info in `data`, the bytes of this frame, as it should be
+    /// stored after the header when `error_protection` is set.
+    pub fn compute_crc(&self, data: &[u8]) -> u16 {
+        let side_info = &data[6..6 + self.header.side_info_length()];
+        crc::crc16_mpeg_update(crc::crc16_mpeg(&data[2..4]), side_info)
+    }
+
     pub fn create_from_buffer(buffer: &mut Buffer) -> Result<Self, ErrorType> {
-        let header = Header::create_from_buffer(buffer);
+        let offset_byte = buffer.pos / 8;
+        let header = Header::create_from_buffer(buffer)?;
         let crc = if header.error_protection {
             Some(buffer.get_bits(16)? as u16)
         } else {
             None
         };
 
-        let side_info = SideInfo::create_from_buffer(buffer, &header.mode)?;
-        let length_byte = 144000 * (header.get_bitrate()? / header.get_frequency()?) as usize
-            + header.padding_bit as usize;
+        let side_info = SideInfo::create_from_buffer(buffer, &header)?;
+        let length_byte = buffer.frame_length(&header)?;
 
         Ok(Self {
             header,
             crc,
             side_info,
+            offset_byte,
             length_byte,
         })
     }
 }
+
+/// Number of frames that must be decoded before `frames[index]` so that its output is exact: one
+/// frame for the IMDCT overlap, plus whatever earlier frames hold the bit reservoir bytes
+/// (`main_data_begin`) that it and the overlap frame refer to.
+pub fn priming_frames(frames: &[Frame], index: usize) -> usize {
+    reservoir_frames(
+        index,
+        |i| frames[i].side_info.main_data_begin as usize,
+        |i| frames[i].main_data_length(),
+    )
+}
+
+/// Same as [`priming_frames`], for frames described only by their `main_data_begin` and main
+/// data length.
+pub fn reservoir_frames(
+    index: usize,
+    main_data_begin: impl Fn(usize) -> usize,
+    main_data_length: impl Fn(usize) -> usize,
+) -> usize {
+    if index == 0 {
+        return 0;
+    }
+
+    let reservoir =
+        |start: usize, end: usize| -> usize { (start..end).map(&main_data_length).sum() };
+
+    let mut start = index - 1;
+    while start > 0
+        && (reservoir(start, index - 1) < main_data_begin(index - 1)
+            || reservoir(start, index) < main_data_begin(index))
+    {
+        start -= 1;
+    }
+
+    index - start
+}
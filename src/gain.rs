@@ -0,0 +1,197 @@
+use crate::{
+    buffer::{self, Buffer},
+    decoder,
+    error::ErrorType,
+    side_info::{self, GLOBAL_GAIN_OFFSET},
+    stream::AudioStream,
+    tag::{self, ApeItem},
+};
+
+/// Change of level of one `global_gain` step, in dB.
+pub const GAIN_STEP_DB: f64 = 1.5;
+
+/// APEv2 items in which MP3Gain keeps the steps applied to the left and right channel, and the
+/// range of `global_gain` in the file.
+const UNDO_KEY: &str = "MP3GAIN_UNDO";
+const MINMAX_KEY: &str = "MP3GAIN_MINMAX";
+
+pub struct GainChange {
+    pub data: Vec<u8>,
+    /// Steps applied to the file in all, with earlier changes, as recorded for undoing them.
+    pub total_steps: i32,
+    /// Frames where `global_gain` went past 0 or 255 and was clamped, which undoing the change
+    /// cannot restore exactly.
+    pub clamped: Vec<usize>,
+    /// Frames whose decoded samples go past full scale after the change.
+    pub clipping: Vec<usize>,
+}
+
+/// Steps recorded in the MP3Gain undo item of `items`, as in `+002,+002,N`.
+fn recorded_steps(items: &[ApeItem]) -> Option<i32> {
+    let item = items.iter().find(|item| item.key == UNDO_KEY)?;
+    let text = core::str::from_utf8(&item.value).ok()?;
+    text.split(',').next()?.parse().ok()
+}
+
+/// Changes the level of the audio of `buffer` by `steps` of [`GAIN_STEP_DB`], without decoding
+/// it: `global_gain` is moved by `steps` in every granule and channel coding anything, and the
+/// side info rewritten in place, with the CRC of protected frames recomputed. The steps applied
+/// so far are recorded in an APEv2 tag, as MP3Gain does, for [`undo_gain`], and the music CRC
+/// of the LAME header follows the new frames. Everything else is copied byte for byte.
+pub fn apply_gain(buffer: &mut Buffer, steps: i32) -> Result<GainChange, ErrorType> {
+    let recorded = recorded_steps(&tag::ape_items(&buffer.data)).unwrap_or(0);
+    change_gain(buffer, steps, recorded + steps)
+}
+
+/// Reverts the steps recorded by [`apply_gain`] and removes its items from the APEv2 tag.
+pub fn undo_gain(buffer: &mut Buffer) -> Result<GainChange, ErrorType> {
+    let recorded =
+        recorded_steps(&tag::ape_items(&buffer.data)).ok_or(ErrorType::MissingUndoTag)?;
+    change_gain(buffer, -recorded, 0)
+}
+
+fn change_gain(buffer: &mut Buffer, steps: i32, total_steps: i32) -> Result<GainChange, ErrorType> {
+    let stream = AudioStream::create_from_buffer(buffer.share())?;
+    let mut data = buffer.data.to_vec();
+    let mut clamped = Vec::new();
+    let (mut min, mut max) = (u8::MAX, u8::MIN);
+
+    for (index, frame) in stream.frames().iter().enumerate() {
+        let header = frame.header();
+        let start = frame.offset_byte();
+        let side_info = 8 * (start + 4 + frame.crc().map_or(0, |_| 2));
+        let mut was_clamped = false;
+
+        for (gr, granule) in frame.side_info().granules.iter().enumerate() {
+            for (ch, channel) in granule.channels().iter().enumerate() {
+                // Nothing is coded in empty granules, which encoders often give the largest
+                // gain.
+                if channel.part_23_length == 0 {
+                    continue;
+                }
+                let gain = channel.global_gain as i32 + steps;
+                was_clamped |= !(0..=255).contains(&gain);
+                let gain = gain.clamp(0, 255) as u8;
+                (min, max) = (min.min(gain), max.max(gain));
+
+                let pos = side_info + side_info::channel_info_offset(header, gr, ch);
+                buffer::write_bits(&mut data, pos + GLOBAL_GAIN_OFFSET, 8, gain as u32);
+            }
+        }
+
+        if frame.crc().is_some() {
+            let crc = frame.compute_crc(&data[start..start + frame.length_byte()]);
+            data[start + 4..start + 6].copy_from_slice(&crc.to_be_bytes());
+        }
+        if was_clamped {
+            clamped.push(index);
+        }
+    }
+
+    // Decoded samples scale by 2^(1/4) with every step.
+    let scale = 2f32.powf(steps as f32 / 4.0);
+    let clipping = decoder::samples(&stream)
+        .enumerate()
+        .filter(|(_, (_, samples))| {
+            samples
+                .iter()
+                .flatten()
+                .any(|sample| (sample * scale).abs() > 1.0)
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut items = tag::ape_items(&data);
+    items.retain(|item| item.key != UNDO_KEY && item.key != MINMAX_KEY);
+    if total_steps != 0 {
+        items.push(ApeItem::text(
+            UNDO_KEY,
+            &format!("{total_steps:+04},{total_steps:+04},N"),
+        ));
+        items.push(ApeItem::text(MINMAX_KEY, &format!("{min:03},{max:03}")));
+    }
+    let tag = if items.is_empty() {
+        Vec::new()
+    } else {
+        tag::ape_tag(&items)
+    };
+    data.splice(tag::ape_tag_range(&data), tag);
+
+    stream.update_music_crc(&mut data);
+
+    Ok(GainChange {
+        data,
+        total_steps,
+        clamped,
+        clipping,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crc;
+
+    #[test]
+    fn test_apply_and_undo_gain() {
+        let original = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3")
+            .data
+            .to_vec();
+
+        let change = apply_gain(&mut Buffer::create_buffer_from_data(original.clone()), 2).unwrap();
+        assert_eq!(change.total_steps, 2);
+        assert!(change.clamped.is_empty());
+        // The file already reaches full scale once decoded.
+        assert!(!change.clipping.is_empty());
+        assert_eq!(
+            change.data.len(),
+            original.len() + tag::ape_tag_range(&change.data).len()
+        );
+
+        let before =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_slice(&original)).unwrap();
+        let after = AudioStream::create_from_buffer(Buffer::create_buffer_from_slice(&change.data))
+            .unwrap();
+        // The music CRC of the LAME header follows the new frames.
+        assert_ne!(
+            after.lame().unwrap().music_crc,
+            before.lame().unwrap().music_crc
+        );
+        let xing = after.xing().unwrap();
+        let body = &change.data[after.frames()[0].offset_byte()..];
+        let body = &body[..body.len() - tag::ape_tag_range(&change.data).len()];
+        assert_eq!(xing.lame.as_ref().unwrap().music_crc, crc::crc16_lame(body));
+        for (before, after) in before.frames().iter().zip(after.frames()) {
+            for (before, after) in before
+                .side_info()
+                .granules
+                .iter()
+                .zip(&after.side_info().granules)
+            {
+                for (before, after) in before.channels().iter().zip(after.channels()) {
+                    let steps = if before.part_23_length == 0 { 0 } else { 2 };
+                    assert_eq!(after.global_gain, before.global_gain + steps);
+                    assert_eq!(after.part_23_length, before.part_23_length);
+                }
+            }
+        }
+
+        // Changes add up, and undoing them restores the file.
+        let change = apply_gain(&mut Buffer::create_buffer_from_data(change.data), -3).unwrap();
+        assert_eq!(change.total_steps, -1);
+        let items = tag::ape_items(&change.data);
+        assert_eq!(items[0], ApeItem::text(UNDO_KEY, "-001,-001,N"));
+
+        let undone = undo_gain(&mut Buffer::create_buffer_from_data(change.data)).unwrap();
+        assert_eq!(undone.total_steps, 0);
+        assert!(undone.data == original);
+        assert_eq!(
+            undo_gain(&mut Buffer::create_buffer_from_data(original)).err(),
+            Some(ErrorType::MissingUndoTag)
+        );
+
+        let change = apply_gain(&mut Buffer::create_buffer_from_data(undone.data), -255).unwrap();
+        assert_eq!(change.clamped.len(), 153);
+        assert!(change.clipping.is_empty());
+    }
+}
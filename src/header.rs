@@ -1,27 +1,42 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
 use crate::buffer::Buffer;
 use crate::error;
 
-const HALF_BITRATE_MPEG1_LAYER3: [u8; 15] =
-    [0, 16, 20, 24, 28, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160];
+const BITRATE_MPEG1_LAYER1: [u16; 15] = [
+    0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+];
+const BITRATE_MPEG1_LAYER2: [u16; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+];
+const BITRATE_MPEG1_LAYER3: [u16; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const BITRATE_MPEG2_LAYER1: [u16; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+];
+const BITRATE_MPEG2_LAYER2_3: [u16; 15] =
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
 
 const FREQUENCY_MPEG1: [u16; 3] = [44100, 48000, 32000];
+const FREQUENCY_MPEG2: [u16; 3] = [22050, 24000, 16000];
+const FREQUENCY_MPEG2_5: [u16; 3] = [11025, 12000, 8000];
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Version {
     MPEG1,
     MPEG2,
+    MPEG2_5,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Layer {
     Layer1,
     Layer2,
     Layer3,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Mode {
     Stereo,
     JointStereo,
@@ -29,7 +44,7 @@ pub enum Mode {
     SingleChannel,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Header {
     pub sync_word: u16,
     pub version: Version,
@@ -49,7 +64,7 @@ pub struct Header {
 }
 
 impl Display for Layer {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let layer = match self {
             Self::Layer3 => "Layer III",
             Self::Layer2 => "Layer II",
@@ -72,10 +87,11 @@ impl Layer {
 }
 
 impl Display for Version {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let version = match self {
             Self::MPEG1 => "MPEG-1",
             Self::MPEG2 => "MPEG-2",
+            Self::MPEG2_5 => "MPEG-2.5",
         };
 
         write!(f, "{version}")
@@ -85,15 +101,16 @@ impl Display for Version {
 impl Version {
     fn decode_version(version: u8) -> Result<Version, error::ErrorType> {
         match version {
-            0 => Ok(Version::MPEG2),
-            1 => Ok(Version::MPEG1),
+            0 => Ok(Version::MPEG2_5),
+            2 => Ok(Version::MPEG2),
+            3 => Ok(Version::MPEG1),
             _ => Err(error::ErrorType::UnknownVersion),
         }
     }
 }
 
 impl Display for Mode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mode = match self {
             Self::Stereo => "Stereo",
             Self::JointStereo => "Joint Stereo",
@@ -119,35 +136,39 @@ impl Mode {
 
 impl Header {
     pub fn validate_header(&self) -> Result<(), error::ErrorType> {
-        if self.sync_word == 0xfff && self.layer == Layer::Layer3 {
+        if self.sync_word >> 1 == 0x7ff && self.layer == Layer::Layer3 {
             return Ok(());
         }
 
         Err(error::ErrorType::InvalidHeader)
     }
 
-    pub fn create_from_buffer(buffer: &mut Buffer) -> Self {
+    pub fn create_from_buffer(buffer: &mut Buffer) -> Result<Self, error::ErrorType> {
         let index = buffer.pos / 8;
+        if index + 4 > buffer.data.len() {
+            return Err(error::ErrorType::OutOfIndex);
+        }
 
         let sync_word =
             ((buffer.data[index] as u16) << 4) | (buffer.data[1 + index] as u16 & 0xf0) >> 4;
-        let version = Version::decode_version((buffer.data[1 + index] & 8) >> 3).unwrap();
-        let layer = Layer::decode_layer((buffer.data[1 + index] & 0b110) >> 1).unwrap();
+        let version = Version::decode_version((buffer.data[1 + index] & 0x18) >> 3)?;
+        let layer = Layer::decode_layer((buffer.data[1 + index] & 0b110) >> 1)?;
         let error_protection = (buffer.data[1 + index] & 1) == 0;
         let bitrate = (buffer.data[2 + index] & 0xf0) >> 4;
         let frequency = (buffer.data[2 + index] & 0xc) >> 2;
-        let padding_bit = ((buffer.data[2 + index] & 0x10) >> 1) == 1;
+        let padding_bit = ((buffer.data[2 + index] & 0b10) >> 1) == 1;
         let private_bit = buffer.data[2 + index] & 1 == 1;
-        let mode = Mode::decode_mode((buffer.data[3 + index] & 0xc0) >> 6).unwrap();
-        let intensity_stereo = (buffer.data[3 + index] & 0x20) >> 5 == 1;
-        let ms_stereo = (buffer.data[3 + index] & 0x10) >> 4 == 1;
+        let mode = Mode::decode_mode((buffer.data[3 + index] & 0xc0) >> 6)?;
+        // Mode extension of Layer III: MS stereo in its high bit, intensity stereo in the low one.
+        let ms_stereo = (buffer.data[3 + index] & 0x20) >> 5 == 1;
+        let intensity_stereo = (buffer.data[3 + index] & 0x10) >> 4 == 1;
         let copy_right = (buffer.data[3 + index] & 0b1000) >> 3 == 1;
         let copy_of_original = (buffer.data[3 + index] & 0b100) >> 2 == 0;
         let emphasis = buffer.data[3 + index] & 0b11;
 
-        buffer.move_pos(32).unwrap();
+        buffer.move_pos(32)?;
 
-        Self {
+        Ok(Self {
             sync_word,
             version,
             layer,
@@ -163,34 +184,130 @@ impl Header {
             copy_of_original,
             emphasis,
             pos: buffer.pos - 32,
-        }
+        })
+    }
+
+    pub fn offset_byte(&self) -> usize {
+        self.pos / 8
+    }
+
+    /// Moves the recorded position, for headers parsed from a window of a larger stream.
+    #[cfg(feature = "async")]
+    pub(crate) fn move_offset(&mut self, bytes: usize) {
+        self.pos += bytes * 8;
     }
 
+    /// Bitrate in kb/sec, 0 for free format streams.
     pub fn get_bitrate(&self) -> Result<u16, error::ErrorType> {
-        if self.version == Version::MPEG1 && self.layer == Layer::Layer3 && self.bitrate < 15 {
-            return Ok(HALF_BITRATE_MPEG1_LAYER3[self.bitrate as usize] as u16 * 2);
-        }
+        let table = match (self.version, self.layer) {
+            (Version::MPEG1, Layer::Layer1) => &BITRATE_MPEG1_LAYER1,
+            (Version::MPEG1, Layer::Layer2) => &BITRATE_MPEG1_LAYER2,
+            (Version::MPEG1, Layer::Layer3) => &BITRATE_MPEG1_LAYER3,
+            (_, Layer::Layer1) => &BITRATE_MPEG2_LAYER1,
+            (_, _) => &BITRATE_MPEG2_LAYER2_3,
+        };
 
-        Err(error::ErrorType::UnknownBitrate)
+        table
+            .get(self.bitrate as usize)
+            .copied()
+            .ok_or(error::ErrorType::UnknownBitrate)
     }
 
     pub fn get_frequency(&self) -> Result<u16, error::ErrorType> {
-        if self.version == Version::MPEG1 && self.frequency < 3 {
-            return Ok(FREQUENCY_MPEG1[self.frequency as usize]);
+        let table = match self.version {
+            Version::MPEG1 => &FREQUENCY_MPEG1,
+            Version::MPEG2 => &FREQUENCY_MPEG2,
+            Version::MPEG2_5 => &FREQUENCY_MPEG2_5,
+        };
+
+        table
+            .get(self.frequency as usize)
+            .copied()
+            .ok_or(error::ErrorType::UnknownFrequency)
+    }
+
+    /// Size in bytes of the padding slot.
+    pub fn slot_length(&self) -> usize {
+        match self.layer {
+            Layer::Layer1 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Frame length in bytes, including the header and the padding slot.
+    ///
+    /// Free format streams (bitrate index 0) do not store their bitrate, so their frame length
+    /// cannot be computed from the header alone and `FreeFormat` is returned; see
+    /// `Buffer::frame_length`.
+    pub fn frame_length(&self) -> Result<usize, error::ErrorType> {
+        let bitrate = self.get_bitrate()? as usize * 1000;
+        if bitrate == 0 {
+            return Err(error::ErrorType::FreeFormat);
+        }
+
+        let frequency = self.get_frequency()? as usize;
+        let slots = self.samples_per_frame() / 8 * bitrate / frequency / self.slot_length();
+
+        Ok((slots + self.padding_bit as usize) * self.slot_length())
+    }
+
+    /// Number of PCM samples per channel decoded from one frame.
+    pub fn samples_per_frame(&self) -> usize {
+        match (self.version, self.layer) {
+            (_, Layer::Layer1) => 384,
+            (_, Layer::Layer2) | (Version::MPEG1, Layer::Layer3) => 1152,
+            (_, Layer::Layer3) => 576,
+        }
+    }
+
+    /// Granules in a frame: two in MPEG-1, one in MPEG-2 and 2.5.
+    pub fn granule_count(&self) -> usize {
+        if self.version == Version::MPEG1 {
+            2
+        } else {
+            1
         }
+    }
 
-        Err(error::ErrorType::UnknownFrequency)
+    /// Channels coded in a frame.
+    pub fn channel_count(&self) -> usize {
+        match self.mode {
+            Mode::SingleChannel => 1,
+            _ => 2,
+        }
+    }
+
+    /// Size of the side information block that follows the header (and CRC).
+    pub fn side_info_length(&self) -> usize {
+        match (self.version, self.mode) {
+            (Version::MPEG1, Mode::SingleChannel) => 17,
+            (Version::MPEG1, _) => 32,
+            (_, Mode::SingleChannel) => 9,
+            (_, _) => 17,
+        }
+    }
+}
+
+/// Bitrate as shown by the `Display` of `Header`.
+struct BitrateLabel(u16);
+
+impl Display for BitrateLabel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            0 => write!(f, "free format"),
+            bitrate => write!(f, "{bitrate}kb/sec"),
+        }
     }
 }
 
 impl Display for Header {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             " \t Version: {}
 \t Layer: {}
 \t Error Protection: {}
-\t Bitrate: {}kb/sec
+\t Bitrate: {}
 \t Frequency: {}Hz
 \t Padding: {}
 \t Set Private Bit: {}
@@ -204,7 +321,7 @@ impl Display for Header {
             self.version,
             self.layer,
             self.error_protection,
-            self.get_bitrate().unwrap(),
+            BitrateLabel(self.get_bitrate().unwrap()),
             self.get_frequency().unwrap(),
             self.padding_bit,
             self.private_bit,
@@ -228,14 +345,14 @@ impl Display for Header {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
     #[test]
     fn test_header_from_buffer() {
         let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
-        let header = Header::create_from_buffer(&mut buffer);
+        let header = Header::create_from_buffer(&mut buffer).unwrap();
 
         assert_eq!(
             header,
@@ -250,12 +367,37 @@ mod test {
                 padding_bit: false,
                 private_bit: false,
                 mode: Mode::JointStereo,
-                intensity_stereo: true,
-                ms_stereo: false,
+                intensity_stereo: false,
+                ms_stereo: true,
                 copy_right: false,
                 copy_of_original: false,
                 emphasis: 0,
             }
         );
     }
+
+    #[test]
+    fn test_frame_length() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let mut header = Header::create_from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(header.frame_length().unwrap(), 417);
+
+        header.padding_bit = true;
+        assert_eq!(header.frame_length().unwrap(), 418);
+
+        header.layer = Layer::Layer1;
+        assert_eq!(header.get_bitrate().unwrap(), 288);
+        assert_eq!(header.frame_length().unwrap(), 4 * (78 + 1));
+
+        header.version = Version::MPEG2;
+        header.layer = Layer::Layer3;
+        header.padding_bit = false;
+        assert_eq!(header.get_bitrate().unwrap(), 80);
+        assert_eq!(header.get_frequency().unwrap(), 22050);
+        assert_eq!(header.frame_length().unwrap(), 261);
+
+        header.bitrate = 0;
+        assert_eq!(header.frame_length(), Err(error::ErrorType::FreeFormat));
+    }
 }
@@ -0,0 +1,243 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    xing::{VbriHeader, XingHeader},
+};
+
+const MAGIC: &[u8; 4] = b"MP3X";
+const FORMAT_VERSION: u16 = 1;
+const SIDECAR_EXTENSION: &str = "mp3idx";
+
+/// Position of one audio frame. `sample` counts samples per channel from the first audio frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub sample: u64,
+    pub main_data_begin: u16,
+}
+
+/// Offsets, sample positions and `main_data_begin` of every audio frame of a file (the Xing/Info
+/// or VBRI frame excluded), keyed by the size and content hash of the file it was built from.
+#[derive(Debug, PartialEq)]
+pub struct SeekIndex {
+    pub file_size: u64,
+    pub content_hash: u64,
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Where [`SeekIndex::open`] got the index from.
+#[derive(Debug)]
+pub enum IndexSource {
+    Loaded,
+    Built,
+    /// Built, but the sidecar could not be written.
+    Unsaved(io::Error),
+}
+
+/// 64-bit FNV-1a over 8-byte words, enough to notice any change to the file.
+pub fn content_hash(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let words = data.chunks_exact(8);
+    let rest = words.remainder();
+
+    let hash = words.fold(OFFSET_BASIS, |hash, word| {
+        (hash ^ u64::from_le_bytes(word.try_into().unwrap())).wrapping_mul(PRIME)
+    });
+    rest.iter()
+        .fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(PRIME))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, ErrorType> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or(ErrorType::InvalidIndex)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(ErrorType::InvalidIndex)
+}
+
+impl SeekIndex {
+    pub fn build(buffer: &mut Buffer) -> Self {
+        let mut frames = buffer.extract_frames();
+        if frames.first().is_some_and(|first| {
+            let data = buffer.frame_data(first);
+            XingHeader::parse(data, first.header()).is_some() || VbriHeader::parse(data).is_some()
+        }) {
+            frames.remove(0);
+        }
+
+        let mut sample = 0;
+        let entries = frames
+            .iter()
+            .map(|frame| {
+                let entry = IndexEntry {
+                    offset: frame.offset_byte() as u64,
+                    sample,
+                    main_data_begin: frame.side_info().main_data_begin,
+                };
+                sample += frame.header().samples_per_frame() as u64;
+                entry
+            })
+            .collect();
+
+        Self {
+            file_size: buffer.data.len() as u64,
+            content_hash: content_hash(&buffer.data),
+            entries,
+        }
+    }
+
+    /// Whether the index was built from `data`.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.file_size == data.len() as u64 && self.content_hash == content_hash(data)
+    }
+
+    /// Serialises the index. Offsets and sample positions are stored as varint deltas, so an
+    /// entry usually takes 5 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(26 + self.entries.len() * 5);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.file_size.to_le_bytes());
+        out.extend_from_slice(&self.content_hash.to_le_bytes());
+        write_varint(&mut out, self.entries.len() as u64);
+
+        let mut previous = IndexEntry {
+            offset: 0,
+            sample: 0,
+            main_data_begin: 0,
+        };
+        for entry in &self.entries {
+            write_varint(&mut out, entry.offset - previous.offset);
+            write_varint(&mut out, entry.sample - previous.sample);
+            write_varint(&mut out, entry.main_data_begin as u64);
+            previous = *entry;
+        }
+
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ErrorType> {
+        if data.len() < 22 || &data[..4] != MAGIC {
+            return Err(ErrorType::InvalidIndex);
+        }
+        if u16::from_le_bytes([data[4], data[5]]) != FORMAT_VERSION {
+            return Err(ErrorType::InvalidIndex);
+        }
+
+        let file_size = u64::from_le_bytes(data[6..14].try_into().unwrap());
+        let content_hash = u64::from_le_bytes(data[14..22].try_into().unwrap());
+
+        let mut pos = 22;
+        let count = read_varint(data, &mut pos)? as usize;
+        let mut entries = Vec::with_capacity(count.min(data.len()));
+
+        let (mut offset, mut sample) = (0, 0);
+        for _ in 0..count {
+            let mut add = |total: u64| {
+                let delta = read_varint(data, &mut pos)?;
+                total.checked_add(delta).ok_or(ErrorType::InvalidIndex)
+            };
+            offset = add(offset)?;
+            sample = add(sample)?;
+            let main_data_begin = read_varint(data, &mut pos)? as u16;
+
+            entries.push(IndexEntry {
+                offset,
+                sample,
+                main_data_begin,
+            });
+        }
+
+        Ok(Self {
+            file_size,
+            content_hash,
+            entries,
+        })
+    }
+
+    /// Sidecar file stored next to `path`, e.g. `song.mp3.mp3idx`.
+    pub fn sidecar_path(path: &Path) -> PathBuf {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(SIDECAR_EXTENSION);
+        sidecar.into()
+    }
+
+    /// Loads the sidecar of `path` if it exists and was built from `data`.
+    pub fn load(path: &Path, data: &[u8]) -> Option<Self> {
+        let index = Self::from_bytes(&fs::read(Self::sidecar_path(path)).ok()?).ok()?;
+        index.matches(data).then_some(index)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(Self::sidecar_path(path), self.to_bytes())
+    }
+
+    /// Loads the sidecar of `path`, or rebuilds it from `buffer` and writes it when it is missing
+    /// or stale.
+    pub fn open(path: &Path, buffer: &mut Buffer) -> (Self, IndexSource) {
+        if let Some(index) = Self::load(path, &buffer.data) {
+            return (index, IndexSource::Loaded);
+        }
+
+        let index = Self::build(buffer);
+        let source = match index.save(path) {
+            Ok(()) => IndexSource::Built,
+            Err(err) => IndexSource::Unsaved(err),
+        };
+
+        (index, source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seek_index_round_trip() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let index = SeekIndex::build(&mut buffer);
+
+        assert_eq!(index.entries.len(), 153);
+        assert_eq!(index.entries[0].offset, 417);
+        assert_eq!(index.entries[1].sample, 1152);
+        assert_eq!(SeekIndex::from_bytes(&index.to_bytes()).unwrap(), index);
+
+        let mut data = buffer.data.to_vec();
+        assert!(index.matches(&data));
+        data[5000] ^= 1;
+        assert!(!index.matches(&data));
+
+        // Deltas adding up past 64 bits are rejected.
+        let mut bytes = index.to_bytes()[..22].to_vec();
+        write_varint(&mut bytes, 2);
+        for _ in 0..2 {
+            write_varint(&mut bytes, u64::MAX);
+            write_varint(&mut bytes, 0);
+            write_varint(&mut bytes, 0);
+        }
+        assert_eq!(SeekIndex::from_bytes(&bytes), Err(ErrorType::InvalidIndex));
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "async")]
+pub mod async_reader;
+pub mod buffer;
+#[cfg(feature = "std")]
+pub mod clipping;
+#[cfg(feature = "std")]
+pub mod concat;
+pub mod crc;
+#[cfg(feature = "std")]
+pub mod decoder;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod dump;
+pub mod error;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod gain;
+pub mod header;
+pub mod huffman;
+#[cfg(feature = "std")]
+pub mod index;
+#[cfg(feature = "std")]
+pub mod loudness;
+#[cfg(feature = "std")]
+pub mod mp3_file;
+#[cfg(feature = "std")]
+pub mod mute;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "std")]
+pub mod report;
+pub mod side_info;
+#[cfg(feature = "std")]
+pub mod silence;
+#[cfg(feature = "std")]
+pub mod spectrogram;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod synthesis;
+pub mod tag;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod xing;
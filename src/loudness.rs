@@ -0,0 +1,418 @@
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Display},
+};
+
+use crate::{decoder, stream::AudioStream};
+
+/// Integrated loudness ReplayGain 2.0 brings tracks to, in LUFS.
+pub const REPLAY_GAIN_REFERENCE: f64 = -18.0;
+
+/// Blocks quieter than this, in LUFS, are left out of the integrated loudness and loudness range.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this many LU below the loudness of those above the absolute gate are left out of the
+/// integrated loudness.
+const RELATIVE_GATE: f64 = -10.0;
+/// Like `RELATIVE_GATE`, for the short-term blocks of the loudness range.
+const RANGE_GATE: f64 = -20.0;
+/// Percentiles of the short-term loudness whose difference is the loudness range.
+const RANGE_PERCENTILES: (f64, f64) = (0.10, 0.95);
+/// Loudness is measured over 100ms segments, four to a momentary block (400ms) and thirty to a
+/// short-term block (3s), both starting every segment.
+const MOMENTARY_SEGMENTS: usize = 4;
+const SHORT_TERM_SEGMENTS: usize = 30;
+
+/// Output samples of the true peak interpolator for every input sample.
+const OVERSAMPLING: usize = 4;
+/// Input samples the interpolator looks at for every output sample.
+const TAPS: usize = 12;
+
+/// A second order IIR filter, in transposed direct form II.
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// The K-weighting filter of ITU-R BS.1770: a high shelf modelling the head, then a high pass.
+/// The coefficients are derived for any sample rate the way libebur128 does.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let (frequency, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * frequency / sample_rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let (frequency, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * frequency / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Interpolates a channel at four times its sample rate to find the peaks between samples, with
+/// a windowed sinc filter.
+#[derive(Clone)]
+pub struct Oversampler {
+    /// The last `TAPS` input samples, newest first.
+    history: [f32; TAPS],
+    /// Coefficients of every output phase, by input sample, newest first.
+    phases: [[f32; TAPS]; OVERSAMPLING],
+}
+
+impl Default for Oversampler {
+    fn default() -> Self {
+        let length = (TAPS * OVERSAMPLING) as f64;
+        let center = length / 2.0;
+        let coefficient = |n: usize| {
+            let x = (n as f64 - center) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let hann = 0.5 - 0.5 * (2.0 * PI * n as f64 / length).cos();
+            (sinc * hann) as f32
+        };
+
+        Self {
+            history: [0.0; TAPS],
+            phases: core::array::from_fn(|phase| {
+                core::array::from_fn(|tap| coefficient(tap * OVERSAMPLING + phase))
+            }),
+        }
+    }
+}
+
+impl Oversampler {
+    /// Input samples by which the interpolated signal lags behind the input.
+    pub const DELAY: usize = TAPS / 2;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the next input sample and returns the largest magnitude of the interpolated samples
+    /// it completes, which lie between `DELAY - 1` and `DELAY` input samples back.
+    pub fn push(&mut self, sample: f32) -> f32 {
+        self.history.copy_within(..TAPS - 1, 1);
+        self.history[0] = sample;
+
+        self.phases
+            .iter()
+            .map(|phase| {
+                phase
+                    .iter()
+                    .zip(&self.history)
+                    .map(|(coefficient, sample)| coefficient * sample)
+                    .sum::<f32>()
+                    .abs()
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Loudness of a mean square `energy`, in LUFS.
+fn lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Mean of the energies of `blocks` at least `gate` loud.
+fn mean_above(blocks: &[f64], gate: f64) -> Option<f64> {
+    let gated: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&energy| lufs(energy) > gate)
+        .collect();
+    (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+}
+
+/// Loudness of `blocks` through the absolute and relative gates, `-inf` if all are quieter
+/// than the absolute gate.
+fn gated_loudness(blocks: &[f64]) -> f64 {
+    let Some(mean) = mean_above(blocks, ABSOLUTE_GATE) else {
+        return f64::NEG_INFINITY;
+    };
+    let gate = (lufs(mean) + RELATIVE_GATE).max(ABSOLUTE_GATE);
+    mean_above(blocks, gate).map_or(f64::NEG_INFINITY, lufs)
+}
+
+/// Difference between the loud and quiet end of the gated short-term loudness, in LU.
+fn loudness_range(blocks: &[f64]) -> f64 {
+    let Some(mean) = mean_above(blocks, ABSOLUTE_GATE) else {
+        return 0.0;
+    };
+    let gate = (lufs(mean) + RANGE_GATE).max(ABSOLUTE_GATE);
+    let mut loudness: Vec<f64> = blocks
+        .iter()
+        .map(|&energy| lufs(energy))
+        .filter(|&loudness| loudness > gate)
+        .collect();
+    if loudness.is_empty() {
+        return 0.0;
+    }
+    loudness.sort_by(f64::total_cmp);
+
+    let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+    percentile(RANGE_PERCENTILES.1) - percentile(RANGE_PERCENTILES.0)
+}
+
+/// Measures decoded audio as EBU R128 (ITU-R BS.1770) describes, fed with the samples of every
+/// channel as they are decoded.
+pub struct Meter {
+    filters: Vec<[Biquad; 2]>,
+    oversamplers: Vec<Oversampler>,
+    /// Samples of every channel in a 100ms segment.
+    segment_length: usize,
+    /// K-weighted energy of the current segment so far, summed over the channels.
+    energy: f64,
+    filled: usize,
+    /// Mean square of every complete segment.
+    segments: Vec<f64>,
+    true_peak: f32,
+}
+
+impl Meter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate as f64); channels],
+            oversamplers: vec![Oversampler::new(); channels],
+            segment_length: (sample_rate as usize).div_ceil(10),
+            energy: 0.0,
+            filled: 0,
+            segments: Vec::new(),
+            true_peak: 0.0,
+        }
+    }
+
+    /// Adds the next samples of every channel, which must all be as long. Channels past those
+    /// the meter was made for are left out.
+    pub fn add(&mut self, channels: &[Vec<f32>]) {
+        let length = channels.first().map_or(0, Vec::len);
+        let channels = &channels[..channels.len().min(self.filters.len())];
+        for i in 0..length {
+            for (ch, samples) in channels.iter().enumerate() {
+                let sample = samples[i];
+                let [shelf, high_pass] = &mut self.filters[ch];
+                let weighted = high_pass.process(shelf.process(sample as f64));
+                self.energy += weighted * weighted;
+
+                let peak = self.oversamplers[ch].push(sample).max(sample.abs());
+                self.true_peak = self.true_peak.max(peak);
+            }
+
+            self.filled += 1;
+            if self.filled == self.segment_length {
+                self.segments.push(self.energy / self.segment_length as f64);
+                self.energy = 0.0;
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// The measurements of everything added; an incomplete last segment is left out.
+    pub fn finish(mut self) -> Loudness {
+        // Let the interpolator run past the last sample.
+        for oversampler in &mut self.oversamplers {
+            for _ in 0..Oversampler::DELAY {
+                self.true_peak = self.true_peak.max(oversampler.push(0.0));
+            }
+        }
+
+        let blocks = |length: usize| -> Vec<f64> {
+            self.segments
+                .windows(length)
+                .map(|segments| segments.iter().sum::<f64>() / length as f64)
+                .collect()
+        };
+        let momentary = blocks(MOMENTARY_SEGMENTS);
+        let short_term = blocks(SHORT_TERM_SEGMENTS);
+        let max = |blocks: &[f64]| lufs(blocks.iter().copied().fold(0.0, f64::max));
+
+        Loudness {
+            integrated: gated_loudness(&momentary),
+            range: loudness_range(&short_term),
+            momentary_max: max(&momentary),
+            short_term_max: max(&short_term),
+            true_peak: 20.0 * (self.true_peak as f64).log10(),
+            blocks: momentary,
+        }
+    }
+}
+
+/// Loudness measurements of a track. Levels without anything to measure, such as the short-term
+/// maximum of tracks under 3s, are `-inf`.
+#[derive(Clone)]
+pub struct Loudness {
+    /// Integrated loudness, in LUFS.
+    pub integrated: f64,
+    /// Loudness range, in LU.
+    pub range: f64,
+    /// Loudest momentary (400ms) block, in LUFS.
+    pub momentary_max: f64,
+    /// Loudest short-term (3s) block, in LUFS.
+    pub short_term_max: f64,
+    /// Largest magnitude of the signal interpolated at four times the sample rate, in dBTP.
+    pub true_peak: f64,
+    /// Energy of every momentary block, which album loudness pools across tracks.
+    blocks: Vec<f64>,
+}
+
+/// A ReplayGain 2.0 adjustment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGain {
+    /// Gain bringing the integrated loudness to [`REPLAY_GAIN_REFERENCE`], in dB; 0 for
+    /// silence.
+    pub gain: f64,
+    /// True peak, as a fraction of full scale.
+    pub peak: f64,
+}
+
+impl ReplayGain {
+    fn create_from_loudness(integrated: f64, true_peak: f64) -> Self {
+        Self {
+            gain: if integrated.is_finite() {
+                REPLAY_GAIN_REFERENCE - integrated
+            } else {
+                0.0
+            },
+            peak: 10f64.powf(true_peak / 20.0),
+        }
+    }
+}
+
+impl Debug for Loudness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Loudness")
+            .field("integrated", &self.integrated)
+            .field("range", &self.range)
+            .field("momentary_max", &self.momentary_max)
+            .field("short_term_max", &self.short_term_max)
+            .field("true_peak", &self.true_peak)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Loudness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Integrated loudness: {:.1} LUFS", self.integrated)?;
+        writeln!(f, "Loudness range: {:.1} LU", self.range)?;
+        writeln!(f, "Momentary max: {:.1} LUFS", self.momentary_max)?;
+        writeln!(f, "Short-term max: {:.1} LUFS", self.short_term_max)?;
+        write!(f, "True peak: {:.1} dBTP", self.true_peak)
+    }
+}
+
+impl Loudness {
+    pub fn replay_gain(&self) -> ReplayGain {
+        ReplayGain::create_from_loudness(self.integrated, self.true_peak)
+    }
+}
+
+/// The album gain of `tracks`: the loudness of all their blocks gated together, and the largest
+/// true peak.
+pub fn album_replay_gain(tracks: &[Loudness]) -> ReplayGain {
+    let blocks: Vec<f64> = tracks
+        .iter()
+        .flat_map(|track| track.blocks.iter().copied())
+        .collect();
+    let true_peak = tracks
+        .iter()
+        .map(|track| track.true_peak)
+        .fold(f64::NEG_INFINITY, f64::max);
+    ReplayGain::create_from_loudness(gated_loudness(&blocks), true_peak)
+}
+
+/// Decodes `stream` and measures its loudness.
+pub fn loudness(stream: &AudioStream) -> Loudness {
+    let header = stream.header();
+    let mut meter = Meter::new(
+        header.get_frequency().unwrap_or(44100) as u32,
+        stream.channel_count(),
+    );
+    for (_, samples) in decoder::samples(stream) {
+        meter.add(&samples);
+    }
+    meter.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_loudness() {
+        // EBU Tech 3341, case 1: a 1kHz sine at -23dBFS in both channels reads -23 LUFS.
+        let sample_rate = 48000;
+        let amplitude = 10f32.powf(-23.0 / 20.0);
+        let sine: Vec<f32> = (0..20 * sample_rate)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / sample_rate as f32).sin()
+            })
+            .collect();
+        let mut meter = Meter::new(sample_rate as u32, 2);
+        meter.add(&[sine.clone(), sine]);
+        let sine = meter.finish();
+        assert!((sine.integrated + 23.0).abs() < 0.1, "{}", sine.integrated);
+        assert!((sine.momentary_max + 23.0).abs() < 0.1);
+        assert!(sine.range < 0.1);
+        assert!((sine.true_peak + 23.0).abs() < 0.1);
+
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+        let track = loudness(&stream);
+        assert!(
+            track.integrated > -30.0 && track.integrated < -5.0,
+            "{track:?}"
+        );
+        assert!(track.momentary_max >= track.integrated);
+        assert!(track.short_term_max.is_finite());
+        // The file has decoded samples at full scale.
+        assert!(track.true_peak >= 0.0);
+
+        let gain = track.replay_gain();
+        assert_eq!(gain.gain, REPLAY_GAIN_REFERENCE - track.integrated);
+        assert!(gain.peak >= 1.0);
+        let album = album_replay_gain(&[track.clone(), track]);
+        assert!((album.gain - gain.gain).abs() < 1e-9);
+        assert_eq!(album.peak, gain.peak);
+
+        assert_eq!(Meter::new(44100, 1).finish().integrated, f64::NEG_INFINITY);
+
+        // Extra channels are left out.
+        let (mut mono, mut stereo) = (Meter::new(44100, 1), Meter::new(44100, 1));
+        mono.add(&[vec![0.5; 44100]]);
+        stereo.add(&[vec![0.5; 44100], vec![1.0; 44100]]);
+        let (mono, stereo) = (mono.finish(), stereo.finish());
+        assert_eq!(mono.integrated, stereo.integrated);
+        assert_eq!(mono.true_peak, stereo.true_peak);
+    }
+}
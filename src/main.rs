@@ -1,24 +1,38 @@
-#![allow(unused_variables, dead_code)]
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    process::exit,
+};
 
-mod buffer;
-mod error;
-mod frame;
-mod header;
-mod side_info;
+use mp3_info::{
+    buffer::Buffer,
+    clipping::{self, ChannelPeaks},
+    concat,
+    concat::Compatibility,
+    diff::{self, FrameDiff},
+    dump, frame,
+    gain::{self, GainChange, GAIN_STEP_DB},
+    index::{IndexSource, SeekIndex},
+    loudness::{self, Loudness},
+    mute::{self, MuteRange},
+    parallel, plot, repair,
+    report::{self, FileInfo, FileReport, Summary},
+    silence::{self, Thresholds},
+    spectrogram, split,
+    split::SplitMode,
+    stream::AudioStream,
+    validate,
+};
 
-use std::{path::PathBuf, process::exit};
-
-use buffer::Buffer;
-use header::Header;
-
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
     /// MP3 file location
-    #[arg(short, long)]
-    file: String,
+    #[arg(short, long, required = true)]
+    file: Option<String>,
 
     /// Number of frames
     #[arg(short, long, default_value_t = true)]
@@ -27,29 +41,819 @@ struct Args {
     /// Frame's header and side info
     #[arg(long, name = "FRAME NUMBER")]
     frame: Option<usize>,
+
+    /// With `--frame`, show the bits of the header and side info with every field annotated
+    #[arg(long, requires = "FRAME NUMBER")]
+    dump: bool,
+
+    /// Browse the frames interactively in the terminal
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with_all = ["FRAME NUMBER", "quick"])]
+    tui: bool,
+
+    /// Count frames and compute the duration from frame headers only
+    #[arg(short, long, conflicts_with = "FRAME NUMBER")]
+    quick: bool,
+
+    /// Decode the file and print its EBU R128 loudness, true peak and ReplayGain
+    #[arg(short, long, conflicts_with_all = ["FRAME NUMBER", "quick"])]
+    loudness: bool,
+
+    /// Map input files into memory instead of reading them, which is faster on large files
+    #[arg(long, global = true)]
+    mmap: bool,
+
+    /// Threads used to scan large files and batches of files [default: one per CPU]
+    #[arg(short, long, global = true)]
+    threads: Option<usize>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Join MP3 files with the same version, layer, sample rate and channel mode
+    Concat {
+        /// MP3 files to join, in order
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<String>,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Split a file at frame boundaries into standalone chunks
+    Split {
+        /// MP3 file location
+        file: String,
+
+        #[command(flatten)]
+        by: SplitBy,
+
+        /// Copy the ID3v2 tag of the input into every chunk
+        #[arg(long)]
+        copy_tag: bool,
+
+        /// Directory to write the chunks to
+        #[arg(short, long, default_value = ".")]
+        output_dir: String,
+    },
+    /// Rewrite (or insert) the Xing/Info header from a rescan of the frames
+    Repair {
+        /// MP3 file location
+        file: String,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Report every integrity problem of a file; exits with 1 if there is any
+    Validate {
+        /// MP3 file location
+        file: String,
+    },
+    /// Compare the audio frames of two files; exits with 1 if they differ
+    Diff {
+        /// First MP3 file location
+        left: String,
+
+        /// Second MP3 file location
+        right: String,
+    },
+    /// Chart bitrate, bit reservoir, part_23_length and block types over time as an SVG file
+    Plot {
+        /// MP3 file location
+        file: String,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Draw the decoded spectral lines over time as a PNG spectrogram
+    Spectrogram {
+        /// MP3 file location
+        file: String,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+
+        /// Draw every channel in its own panel instead of averaging them
+        #[arg(long)]
+        per_channel: bool,
+
+        /// Width of the image in pixels, three per granule up to 1600 by default
+        #[arg(long)]
+        width: Option<usize>,
+    },
+    /// Change the volume without re-encoding, in steps of 1.5dB, or undo an earlier change
+    Gain {
+        /// MP3 file location
+        file: String,
+
+        #[command(flatten)]
+        change: GainBy,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Mute or fade out time ranges without re-encoding, listing every granule changed
+    Mute {
+        /// MP3 file location
+        file: String,
+
+        /// Range to mute, as START-END in seconds (repeat for several)
+        #[arg(long = "range", required = true, value_parser = parse_range)]
+        ranges: Vec<MuteRange>,
+
+        /// Seconds over which the level ramps down at the start of a range and back up at its end
+        #[arg(long, default_value_t = 0.0)]
+        fade: f64,
+
+        /// Also replace the coded values of muted granules by zeros
+        #[arg(long)]
+        clear: bool,
+
+        /// Output file location
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Measure the loudness of files and their ReplayGain 2.0 track and album gain
+    Loudness {
+        /// MP3 files of the album
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /// Find silent passages and dropouts from the side info, exiting with 1 when there are any
+    Silence {
+        /// MP3 file location
+        file: String,
+
+        /// Shortest silence reported, in seconds
+        #[arg(long, default_value_t = 2.0)]
+        min_silence: f64,
+
+        /// Shortest dropout reported, in seconds: a shorter silence with audio on both sides
+        #[arg(long, default_value_t = 0.01)]
+        min_dropout: f64,
+
+        /// Granules without big values coding at most this many bits are silent
+        #[arg(long, default_value_t = 64)]
+        max_bits: u16,
+
+        /// Granules whose `global_gain` keeps them under this level are silent, in dBFS
+        #[arg(long, default_value_t = -60.0, allow_negative_numbers = true)]
+        max_level: f64,
+
+        /// Decode the passages found and keep those whose RMS level stays under this many dBFS
+        #[arg(long, value_name = "DBFS", num_args = 0..=1, require_equals = true, default_missing_value = "-60", allow_negative_numbers = true)]
+        confirm: Option<f64>,
+    },
+    /// Decode a file and list its clipped samples and inter-sample overs, exiting with 1 when
+    /// there are any
+    Clipping {
+        /// MP3 file location
+        file: String,
+
+        /// Also fail when the true peak of a channel is above this many dBTP
+        #[arg(long, value_name = "DBTP", allow_negative_numbers = true)]
+        max_true_peak: Option<f64>,
+    },
+    /// Build (or refresh) the seek index sidecar file of a file
+    Index {
+        /// MP3 file location
+        file: String,
+    },
+    /// Report on many files concurrently, with totals over all of them
+    Batch {
+        /// MP3 files, directories to search recursively for .mp3 files, or patterns such as
+        /// `music/**/*.mp3` (quoted, so that the shell does not expand them)
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct SplitBy {
+    /// Length of every chunk in seconds
+    #[arg(long)]
+    seconds: Option<f64>,
+
+    /// Maximum size of the audio frames of every chunk in bytes
+    #[arg(long)]
+    bytes: Option<usize>,
+
+    /// Cut in the middle of quiet passages lasting at least this many seconds
+    #[arg(long, value_name = "MIN_SECONDS")]
+    silence: Option<f64>,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct GainBy {
+    /// Gain in dB, rounded to the nearest multiple of 1.5dB
+    #[arg(long, allow_negative_numbers = true)]
+    db: Option<f64>,
+
+    /// Revert the changes recorded in the APEv2 tag of the file
+    #[arg(long)]
+    undo: bool,
+}
+
+impl SplitBy {
+    fn mode(&self) -> SplitMode {
+        match (self.seconds, self.bytes, self.silence) {
+            (Some(seconds), _, _) => SplitMode::Duration(seconds),
+            (_, Some(bytes), _) => SplitMode::Size(bytes),
+            (_, _, Some(seconds)) => SplitMode::Silence(seconds),
+            _ => unreachable!("clap requires one of the split modes"),
+        }
+    }
+}
+
+fn parse_range(range: &str) -> Result<MuteRange, String> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or("expected START-END in seconds")?;
+    let seconds = |value: &str| value.trim().parse::<f64>().map_err(|err| err.to_string());
+    let (start, end) = (seconds(start)?, seconds(end)?);
+    if end <= start {
+        return Err("the end must come after the start".into());
+    }
+
+    Ok(MuteRange { start, end })
+}
+
+fn open_buffer(path: &str, mmap: bool) -> Buffer<'static> {
+    if mmap {
+        Buffer::create_buffer_from_mapped_file(path)
+    } else {
+        Buffer::create_buffer_from_file(path)
+    }
+}
+
+fn try_open_buffer(path: &str, mmap: bool) -> io::Result<Buffer<'static>> {
+    if mmap {
+        Buffer::try_create_buffer_from_mapped_file(path)
+    } else {
+        Buffer::try_create_buffer_from_file(path)
+    }
+}
+
+fn concat(inputs: &[String], output: &str, mmap: bool) {
+    let inputs: Vec<AudioStream> = inputs
+        .iter()
+        .map(|path| {
+            AudioStream::create_from_buffer(open_buffer(path, mmap)).unwrap_or_else(|err| {
+                eprintln!("Cannot read frames from `{path}`: {err:?}");
+                exit(-1);
+            })
+        })
+        .collect();
+
+    for (i, input) in inputs.iter().enumerate().skip(1) {
+        match Compatibility::check(inputs[0].header(), input.header()) {
+            Compatibility::Compatible => {}
+            Compatibility::StereoModeDiffers => eprintln!(
+                "Warning: input {i} is {} while input 0 is {}.",
+                input.header().mode,
+                inputs[0].header().mode
+            ),
+            Compatibility::Incompatible => {
+                eprintln!(
+                    "Input {i} ({} {}, {}Hz, {}) cannot be joined with input 0 ({} {}, {}Hz, {}).",
+                    input.header().version,
+                    input.header().layer,
+                    input.header().get_frequency().unwrap_or(0),
+                    input.header().mode,
+                    inputs[0].header().version,
+                    inputs[0].header().layer,
+                    inputs[0].header().get_frequency().unwrap_or(0),
+                    inputs[0].header().mode,
+                );
+                exit(-1);
+            }
+        }
+    }
+
+    let joined = concat::concat(&inputs).unwrap_or_else(|err| {
+        eprintln!("Cannot join files: {err:?}");
+        exit(-1);
+    });
+    if joined.gapless_capped {
+        eprintln!(
+            "Warning: the encoder delay or padding of the inputs adds up to more than the 4095 samples the LAME header holds; gapless players will not trim the output exactly."
+        );
+    }
+
+    fs::write(output, joined.data).expect("Cannot write output file");
+}
+
+fn split(file: &str, mode: &SplitMode, copy_tag: bool, output_dir: &str, mmap: bool) {
+    let stream = AudioStream::create_from_buffer(open_buffer(file, mmap)).unwrap_or_else(|err| {
+        eprintln!("Cannot read frames from `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let chunks = split::split(&stream, mode, copy_tag).unwrap_or_else(|err| {
+        eprintln!("Cannot split file: {err:?}");
+        exit(-1);
+    });
+
+    let stem = Path::new(file)
+        .file_stem()
+        .map_or("chunk".into(), |stem| stem.to_string_lossy());
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let path = Path::new(output_dir).join(format!("{stem}_{i:03}.mp3"));
+        fs::write(&path, chunk).expect("Cannot write output file");
+        println!("{}: {} bytes", path.display(), chunk.len());
+    }
+}
+
+fn repair(file: &str, output: &str, mmap: bool) {
+    let mut buffer = open_buffer(file, mmap);
+    let repair = repair::repair(&mut buffer).unwrap_or_else(|err| {
+        eprintln!("Cannot repair `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    match &repair.previous {
+        None if repair.previous_vbri.is_some() => println!(
+            "VBRI header replaced by a Xing header: {} frames, {} bytes",
+            repair.current.frames.unwrap_or(0),
+            repair.current.bytes.unwrap_or(0),
+        ),
+        Some(previous) => println!(
+            "Xing header: {} frames, {} bytes -> {} frames, {} bytes",
+            previous.frames.unwrap_or(0),
+            previous.bytes.unwrap_or(0),
+            repair.current.frames.unwrap_or(0),
+            repair.current.bytes.unwrap_or(0),
+        ),
+        None => println!(
+            "Xing header inserted: {} frames, {} bytes",
+            repair.current.frames.unwrap_or(0),
+            repair.current.bytes.unwrap_or(0),
+        ),
+    }
+
+    fs::write(output, repair.data).expect("Cannot write output file");
+}
+
+fn validate(file: &str, mmap: bool) {
+    let mut buffer = open_buffer(file, mmap);
+    let issues = validate::validate(&mut buffer);
+
+    if issues.is_empty() {
+        println!("No problems found.");
+        return;
+    }
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+    eprintln!("\n{} problem(s) found.", issues.len());
+    exit(1);
+}
+
+fn diff(left: &str, right: &str, mmap: bool) {
+    let [left_stream, right_stream] = [left, right].map(|path| {
+        AudioStream::create_from_buffer(open_buffer(path, mmap)).unwrap_or_else(|err| {
+            eprintln!("Cannot read frames from `{path}`: {err:?}");
+            exit(-1);
+        })
+    });
+    let left_offset = |i: usize| left_stream.frames()[i].offset_byte();
+    let right_offset = |i: usize| right_stream.frames()[i].offset_byte();
+
+    let diffs = diff::diff(&left_stream, &right_stream);
+    if diffs.is_empty() {
+        println!("Audio frames are identical.");
+        return;
+    }
+
+    let (mut changed, mut removed, mut inserted) = (0, 0, 0);
+    for frame_diff in &diffs {
+        match frame_diff {
+            FrameDiff::Removed { left: i } => {
+                removed += 1;
+                println!("{i} ({:#010x}) only in `{left}`", left_offset(*i));
+            }
+            FrameDiff::Inserted { right: j } => {
+                inserted += 1;
+                println!("{j} ({:#010x}) only in `{right}`", right_offset(*j));
+            }
+            FrameDiff::Changed {
+                left: i,
+                right: j,
+                differences,
+            } => {
+                changed += 1;
+                println!(
+                    "{i} ({:#010x}) / {j} ({:#010x}) differ:",
+                    left_offset(*i),
+                    right_offset(*j)
+                );
+                for difference in differences {
+                    println!("\t {difference}");
+                }
+            }
+        }
+    }
+
+    eprintln!(
+        "\n{changed} frame(s) changed, {removed} only in `{left}`, {inserted} only in `{right}`."
+    );
+    exit(1);
+}
+
+fn plot(file: &str, output: &str, mmap: bool) {
+    let stream = AudioStream::create_from_buffer(open_buffer(file, mmap)).unwrap_or_else(|err| {
+        eprintln!("Cannot read frames from `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let title = Path::new(file)
+        .file_name()
+        .map_or(file.into(), |name| name.to_string_lossy());
+    fs::write(output, plot::plot(stream.frames(), &title)).expect("Cannot write output file");
+    println!("{output}: {} frames", stream.frames().len());
+}
+
+fn spectrogram(file: &str, output: &str, per_channel: bool, width: Option<usize>, mmap: bool) {
+    let stream = AudioStream::create_from_buffer(open_buffer(file, mmap)).unwrap_or_else(|err| {
+        eprintln!("Cannot read frames from `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let image = spectrogram::spectrogram(&stream, per_channel, width);
+    let out = fs::File::create(output).expect("Cannot create output file");
+    image
+        .write_png(io::BufWriter::new(out))
+        .expect("Cannot write output file");
+    println!("{output}: {}x{} pixels", image.width, image.height);
+}
+
+fn gain(file: &str, change: &GainBy, output: &str, mmap: bool) {
+    let mut buffer = open_buffer(file, mmap);
+    let result = match change.db {
+        Some(db) => gain::apply_gain(&mut buffer, (db / GAIN_STEP_DB).round() as i32),
+        None => gain::undo_gain(&mut buffer),
+    };
+    let GainChange {
+        data,
+        total_steps,
+        clamped,
+        clipping,
+    } = result.unwrap_or_else(|err| {
+        eprintln!("Cannot change the gain of `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let header = AudioStream::create_from_buffer(Buffer::create_buffer_from_slice(&data))
+        .map(|stream| stream.header().clone())
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot read frames from the result: {err:?}");
+            exit(-1);
+        });
+    let seconds = |frame: usize| {
+        frame as f64 * header.samples_per_frame() as f64
+            / header.get_frequency().unwrap_or(44100) as f64
+    };
+    let list = |frames: &[usize]| {
+        frames
+            .iter()
+            .map(|&frame| format!("{frame} ({:.3}s)", seconds(frame)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    fs::write(output, data).expect("Cannot write output file");
+    println!(
+        "{output}: {total_steps:+} steps ({:+.1} dB) from the original",
+        total_steps as f64 * GAIN_STEP_DB
+    );
+    if !clamped.is_empty() {
+        println!(
+            "{} frame(s) clamped, which cannot be undone exactly: {}",
+            clamped.len(),
+            list(&clamped)
+        );
+    }
+    if !clipping.is_empty() {
+        println!(
+            "{} frame(s) clip once decoded: {}",
+            clipping.len(),
+            list(&clipping)
+        );
+    }
+}
+
+fn mute(file: &str, ranges: &[MuteRange], fade: f64, clear: bool, output: &str, mmap: bool) {
+    let mut buffer = open_buffer(file, mmap);
+    let edit = mute::mute(&mut buffer, ranges, fade, clear).unwrap_or_else(|err| {
+        eprintln!("Cannot mute `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    for granule in &edit.granules {
+        let gains: Vec<String> = granule
+            .gains
+            .iter()
+            .map(|(before, after)| format!("{before} -> {after}"))
+            .collect();
+        println!(
+            "frame {} granule {} at {:.3}s: global_gain {}{}",
+            granule.frame,
+            granule.granule,
+            granule.time,
+            gains.join(", "),
+            if granule.cleared { ", cleared" } else { "" }
+        );
+    }
+
+    fs::write(output, &edit.data).expect("Cannot write output file");
+    eprintln!("\n{output}: {} granule(s) changed.", edit.granules.len());
+}
+
+fn silence(file: &str, thresholds: &Thresholds, mmap: bool) {
+    let stream = AudioStream::create_from_buffer(open_buffer(file, mmap)).unwrap_or_else(|err| {
+        eprintln!("Cannot read frames from `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let gaps = silence::detect(&stream, thresholds);
+    if gaps.is_empty() {
+        println!("No silence found.");
+        return;
+    }
+
+    for gap in &gaps {
+        println!("{gap}");
+    }
+    eprintln!("\n{} passage(s) found.", gaps.len());
+    exit(1);
+}
+
+fn clipping(file: &str, max_true_peak: Option<f64>, mmap: bool) {
+    let stream = AudioStream::create_from_buffer(open_buffer(file, mmap)).unwrap_or_else(|err| {
+        eprintln!("Cannot read frames from `{file}`: {err:?}");
+        exit(-1);
+    });
+
+    let channels = clipping::clipping(&stream);
+    for (ch, peaks) in channels.iter().enumerate() {
+        println!("Channel {ch}: {peaks}");
+        for over in &peaks.clipped {
+            println!(
+                "  clipped at {:.3}s (sample {}): {:.4}",
+                over.time, over.sample, over.value
+            );
+        }
+        for over in &peaks.inter_sample {
+            println!(
+                "  inter-sample over at {:.3}s (after sample {}): {:.4}",
+                over.time, over.sample, over.value
+            );
+        }
+    }
+
+    let too_loud = |peaks: &ChannelPeaks| max_true_peak.is_some_and(|max| peaks.true_peak > max);
+    let failed = channels
+        .iter()
+        .filter(|peaks| !peaks.is_clean() || too_loud(peaks))
+        .count();
+    if failed > 0 {
+        eprintln!("\n{failed} channel(s) clip or peak too high.");
+        exit(1);
+    }
+}
+
+fn loudness(inputs: &[String], threads: usize, mmap: bool) {
+    let paths: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+    let results = parallel::process_files(&paths, threads, |path| {
+        let path = path.to_string_lossy();
+        let buffer = try_open_buffer(&path, mmap).map_err(|err| err.to_string())?;
+        let stream = AudioStream::create_from_buffer(buffer)
+            .map_err(|err| format!("cannot read frames: {err:?}"))?;
+        Ok::<_, String>(loudness::loudness(&stream))
+    });
+
+    // Files that cannot be read are left out of the album.
+    let mut tracks: Vec<Loudness> = Vec::new();
+    for (path, result) in inputs.iter().zip(results) {
+        let track = match result {
+            Ok(track) => track,
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                continue;
+            }
+        };
+        let gain = track.replay_gain();
+        println!(
+            "{path}: {:.1} LUFS, range {:.1} LU, momentary max {:.1} LUFS, short-term max {:.1} LUFS, true peak {:.1} dBTP, track gain {:+.2} dB, peak {:.6}",
+            track.integrated,
+            track.range,
+            track.momentary_max,
+            track.short_term_max,
+            track.true_peak,
+            gain.gain,
+            gain.peak
+        );
+        tracks.push(track);
+    }
+
+    if tracks.is_empty() {
+        exit(-1);
+    }
+    let album = loudness::album_replay_gain(&tracks);
+    println!("Album gain {:+.2} dB, peak {:.6}", album.gain, album.peak);
+}
+
+fn index(file: &str, mmap: bool) {
+    let mut buffer = open_buffer(file, mmap);
+    let (index, source) = SeekIndex::open(Path::new(file), &mut buffer);
+    if let IndexSource::Unsaved(err) = &source {
+        eprintln!("Cannot write seek index: {err}");
+    }
+
+    println!(
+        "{} seek index `{}`: {} frames",
+        match source {
+            IndexSource::Loaded => "Loaded",
+            IndexSource::Built | IndexSource::Unsaved(_) => "Built",
+        },
+        SeekIndex::sidecar_path(Path::new(file)).display(),
+        index.entries.len()
+    );
+}
+
+fn batch(inputs: &[String], format: ReportFormat, threads: usize, mmap: bool) {
+    let (paths, failed) = report::collect_files(inputs);
+
+    let results = parallel::process_files(&paths, threads, |path| {
+        let path = path.to_string_lossy();
+        let mut buffer = try_open_buffer(&path, mmap).map_err(|err| err.to_string())?;
+
+        FileInfo::scan(&mut buffer).map_err(|err| format!("{err:?}"))
+    });
+
+    let reports: Vec<FileReport> = paths
+        .into_iter()
+        .zip(results)
+        .map(|(path, result)| FileReport { path, result })
+        .chain(failed)
+        .collect();
+    let summary = Summary::create_from_reports(&reports);
+
+    let mut out = io::stdout().lock();
+    match format {
+        ReportFormat::Table => report::write_table(&mut out, &reports, &summary),
+        ReportFormat::Json => report::write_json(&mut out, &reports, &summary),
+        ReportFormat::Csv => report::write_csv(&mut out, &reports),
+    }
+    .expect("Cannot write report");
 }
 
 fn main() {
     let args = Args::parse();
+    let threads = args.threads.unwrap_or_else(parallel::default_threads);
+
+    match &args.command {
+        Some(Command::Concat { inputs, output }) => return concat(inputs, output, args.mmap),
+        Some(Command::Split {
+            file,
+            by,
+            copy_tag,
+            output_dir,
+        }) => return split(file, &by.mode(), *copy_tag, output_dir, args.mmap),
+        Some(Command::Repair { file, output }) => return repair(file, output, args.mmap),
+        Some(Command::Validate { file }) => return validate(file, args.mmap),
+        Some(Command::Diff { left, right }) => return diff(left, right, args.mmap),
+        Some(Command::Plot { file, output }) => return plot(file, output, args.mmap),
+        Some(Command::Spectrogram {
+            file,
+            output,
+            per_channel,
+            width,
+        }) => return spectrogram(file, output, *per_channel, *width, args.mmap),
+        Some(Command::Gain {
+            file,
+            change,
+            output,
+        }) => return gain(file, change, output, args.mmap),
+        Some(Command::Mute {
+            file,
+            ranges,
+            fade,
+            clear,
+            output,
+        }) => return mute(file, ranges, *fade, *clear, output, args.mmap),
+        Some(Command::Loudness { inputs }) => return loudness(inputs, threads, args.mmap),
+        Some(Command::Silence {
+            file,
+            min_silence,
+            min_dropout,
+            max_bits,
+            max_level,
+            confirm,
+        }) => {
+            let thresholds = Thresholds {
+                max_bits: *max_bits,
+                max_level_db: *max_level,
+                min_silence: *min_silence,
+                min_dropout: *min_dropout,
+                confirm_rms_db: *confirm,
+            };
+            return silence(file, &thresholds, args.mmap);
+        }
+        Some(Command::Clipping {
+            file,
+            max_true_peak,
+        }) => return clipping(file, *max_true_peak, args.mmap),
+        Some(Command::Index { file }) => return index(file, args.mmap),
+        Some(Command::Batch { inputs, format }) => {
+            return batch(inputs, *format, threads, args.mmap)
+        }
+        None => {}
+    }
+
+    let file = args.file.unwrap();
 
-    if !PathBuf::from(&args.file).exists() {
-        eprintln!("\n`{}` does not exist.", &args.file);
+    if !PathBuf::from(&file).exists() {
+        eprintln!("\n`{}` does not exist.", &file);
     }
 
-    let mut buffer = Buffer::create_buffer_from_file(&args.file);
-    let frames = buffer.extract_frames();
+    let mut buffer = open_buffer(&file, args.mmap);
+
+    if args.quick {
+        let records = buffer.scan_audio_headers();
+        println!("\nNumber of frames: {}", records.len());
+        println!("Duration: {:.3}s\n", frame::duration(&records));
+        return;
+    }
+
+    let frames = parallel::extract_frames(&mut buffer, threads);
+
+    #[cfg(feature = "tui")]
+    if args.tui {
+        mp3_info::tui::browse(&frames).expect("Cannot run the frame browser");
+        return;
+    }
 
     if args.count {
         println!("\nNumber of frames: {}\n", frames.len());
     }
 
+    if args.loudness {
+        let stream = AudioStream::create_from_frames(buffer, frames).unwrap_or_else(|err| {
+            eprintln!("Cannot read frames from `{file}`: {err:?}");
+            exit(-1);
+        });
+        let loudness = loudness::loudness(&stream);
+        let gain = loudness.replay_gain();
+        println!("{loudness}");
+        println!("ReplayGain: {:+.2} dB, peak {:.6}\n", gain.gain, gain.peak);
+        return;
+    }
+
     if let Some(frame_number) = args.frame {
         if frame_number >= frames.len() {
             eprintln!("Frame Number is not in range: 0-{}", frames.len() - 1);
             exit(-1);
         }
 
-        println!("Header:\n{}", frames[frame_number].header());
-        println!("Side Info:\n{}", frames[frame_number].side_info());
+        let frame = &frames[frame_number];
+        if args.dump {
+            let data = buffer.frame_data(frame);
+            let fields = dump::frame_fields(data).unwrap_or_else(|err| {
+                eprintln!("Cannot read the fields of frame {frame_number}: {err:?}");
+                exit(-1);
+            });
+
+            let mut out = io::stdout().lock();
+            let color = out.is_terminal();
+            dump::write_dump(&mut out, frame.offset_byte(), data, &fields, color)
+                .expect("Cannot write dump");
+            return;
+        }
+
+        println!("Header:\n{}", frame.header());
+        println!("Side Info:\n{}", frame.side_info());
     }
 }
@@ -0,0 +1,334 @@
+use std::path::Path;
+
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    frame::{self, Frame},
+    header::Header,
+    index::{IndexEntry, SeekIndex},
+    tag,
+    xing::{VbriHeader, XingHeader},
+};
+
+/// Delay of the synthesis filterbank, in samples. Gapless players drop it on top of the encoder
+/// delay stored in the LAME header.
+pub const DECODER_DELAY: u64 = 529;
+
+/// Samples a gapless player drops from the decoder output before sample 0: the encoder delay
+/// of the LAME header of `xing`, or of `vbri` without one, plus [`DECODER_DELAY`], or 0 when
+/// there is neither.
+pub fn gapless_delay(xing: Option<&XingHeader>, vbri: Option<&VbriHeader>) -> u64 {
+    match (xing, vbri) {
+        (Some(xing), _) => xing
+            .lame
+            .as_ref()
+            .map_or(0, |lame| lame.encoder_delay as u64 + DECODER_DELAY),
+        (None, Some(vbri)) => vbri.delay as u64 + DECODER_DELAY,
+        (None, None) => 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accuracy {
+    /// Found from the position of every frame, through a scan or a seek index.
+    Exact,
+    /// Interpolated from the Xing or VBRI table of contents. The Xing table has one entry per
+    /// percent of the duration, stored with a resolution of 1/256 of the file size; the VBRI
+    /// table one entry per run of frames. Within an entry the bitrate is assumed constant, so
+    /// the frame found and the samples to discard can be off by the bitrate variation inside
+    /// it. The bit reservoir is refilled from a short scan backwards, which is exact unless the
+    /// frames around the target are much smaller than the target frame.
+    Estimated,
+}
+
+/// Where decoding has to start to output a given sample exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeekPoint {
+    /// Byte offset of the first frame to decode, or to keep when cutting.
+    pub offset: usize,
+    /// Byte offset of the frame holding the requested sample.
+    pub frame_offset: usize,
+    /// Decoded samples to drop, counted from the start of the frame at `offset`.
+    pub discard: u64,
+    pub accuracy: Accuracy,
+}
+
+/// An MP3 file opened for seeking. Frame positions come from a seek index or a full scan when
+/// available, and from the Xing/VBRI table of contents otherwise.
+pub struct Mp3File<'a> {
+    buffer: Buffer<'a>,
+    header: Header,
+    audio_start: usize,
+    delay: u64,
+    toc: Option<Vec<(u64, u64)>>,
+    entries: Option<Vec<IndexEntry>>,
+}
+
+/// First valid frame starting at or after byte `from`.
+fn read_frame(buffer: &mut Buffer, from: usize) -> Option<Frame> {
+    buffer.set_pos(from * 8).ok()?;
+    loop {
+        let offset = buffer.set_pos_next_frame().ok()?;
+        match Frame::create_from_buffer(buffer) {
+            Ok(frame) if frame.header().validate_header().is_ok() => return Some(frame),
+            _ => buffer.pos = (offset + 1) * 8,
+        }
+    }
+}
+
+/// Chain of consecutive frames starting at or after byte `from` and ending exactly at byte `to`,
+/// or nothing when no such chain exists.
+fn frames_up_to(buffer: &mut Buffer, from: usize, to: usize) -> Vec<Frame> {
+    let mut search = from;
+    while search < to {
+        let Some(first) = read_frame(buffer, search) else {
+            break;
+        };
+        search = first.offset_byte() + 1;
+
+        let mut chain = vec![first];
+        let end = loop {
+            let last = chain.last().unwrap();
+            let end = last.offset_byte() + last.length_byte();
+            if end >= to {
+                break end;
+            }
+
+            buffer.pos = end * 8;
+            match Frame::create_from_buffer(buffer) {
+                Ok(frame) if frame.header().validate_header().is_ok() => chain.push(frame),
+                _ => break end,
+            }
+        };
+
+        if end == to {
+            return chain;
+        }
+    }
+
+    Vec::new()
+}
+
+/// Piecewise linear interpolation of `x` between `points`, sorted by `x`.
+fn interpolate(points: &[(u64, u64)], x: u64) -> u64 {
+    let i = points
+        .partition_point(|point| point.0 <= x)
+        .clamp(1, points.len() - 1);
+    let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+    if x1 <= x0 {
+        return y0;
+    }
+
+    y0 + y1.saturating_sub(y0) * (x.min(x1) - x0) / (x1 - x0)
+}
+
+impl<'a> Mp3File<'a> {
+    /// Reads the first frames of `buffer`, without scanning the rest of it.
+    pub fn create_from_buffer(mut buffer: Buffer<'a>) -> Result<Self, ErrorType> {
+        let audio_start = tag::id3v2_length(&buffer.data);
+        let first = read_frame(&mut buffer, audio_start).ok_or(ErrorType::NoFrames)?;
+        let data = buffer.frame_data(&first);
+        let xing = XingHeader::parse(data, first.header());
+        let vbri = VbriHeader::parse(data);
+
+        let samples_per_frame = first.header().samples_per_frame();
+        let base = first.offset_byte() as u64;
+        let toc = xing
+            .as_ref()
+            .and_then(|xing| xing.seek_points(samples_per_frame))
+            .or_else(|| vbri.as_ref()?.seek_points(samples_per_frame))
+            .map(|points| {
+                points
+                    .into_iter()
+                    .map(|(sample, byte)| (sample, base + byte))
+                    .collect()
+            });
+
+        let delay = gapless_delay(xing.as_ref(), vbri.as_ref());
+
+        let audio = if xing.is_some() || vbri.is_some() {
+            read_frame(&mut buffer, first.offset_byte() + first.length_byte())
+                .ok_or(ErrorType::NoFrames)?
+        } else {
+            first
+        };
+
+        Ok(Self {
+            buffer,
+            header: audio.header().clone(),
+            audio_start: audio.offset_byte(),
+            delay,
+            toc,
+            entries: None,
+        })
+    }
+
+    /// Opens `path`, using its seek index sidecar when it is up to date.
+    pub fn open(path: &str) -> Result<Self, ErrorType> {
+        let mut file = Self::create_from_buffer(Buffer::create_buffer_from_file(path))?;
+        file.entries =
+            SeekIndex::load(Path::new(path), &file.buffer.data).map(|index| index.entries);
+
+        Ok(file)
+    }
+
+    /// Uses `index` for exact seeking. It must have been built from this file.
+    pub fn set_index(&mut self, index: SeekIndex) -> Result<(), ErrorType> {
+        if !index.matches(&self.buffer.data) {
+            return Err(ErrorType::InvalidIndex);
+        }
+
+        self.entries = Some(index.entries);
+        Ok(())
+    }
+
+    /// Scans every frame for exact seeking.
+    pub fn scan(&mut self) {
+        self.buffer.pos = 0;
+        self.entries = Some(SeekIndex::build(&mut self.buffer).entries);
+    }
+
+    /// Header of the first audio frame.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Samples a gapless player drops from the decoder output before sample 0; see
+    /// [`gapless_delay`].
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    /// Finds where decoding has to start to output `sample`, counted per channel from the first
+    /// sample a gapless player outputs. Without an index or a table of contents the file is
+    /// scanned first.
+    pub fn seek_to_sample(&mut self, sample: u64) -> Result<SeekPoint, ErrorType> {
+        if self.entries.is_none() && self.toc.is_none() {
+            self.scan();
+        }
+
+        let target = sample + self.delay;
+        match &self.entries {
+            Some(entries) => self.seek_exact(entries, target),
+            None => self.seek_estimated(target),
+        }
+    }
+
+    pub fn seek_to_time(&mut self, seconds: f64) -> Result<SeekPoint, ErrorType> {
+        let frequency = self.header.get_frequency()? as f64;
+        self.seek_to_sample((seconds.max(0.0) * frequency).round() as u64)
+    }
+
+    fn seek_exact(&self, entries: &[IndexEntry], target: u64) -> Result<SeekPoint, ErrorType> {
+        let samples_per_frame = self.header.samples_per_frame() as u64;
+        let last = entries.last().ok_or(ErrorType::NoFrames)?;
+        if target >= last.sample + samples_per_frame {
+            return Err(ErrorType::OutOfIndex);
+        }
+
+        let index = entries.partition_point(|entry| entry.sample <= target) - 1;
+        let overhead =
+            4 + if self.header.error_protection { 2 } else { 0 } + self.header.side_info_length();
+        let priming = frame::reservoir_frames(
+            index,
+            |i| entries[i].main_data_begin as usize,
+            |i| ((entries[i + 1].offset - entries[i].offset) as usize).saturating_sub(overhead),
+        );
+        let start = &entries[index - priming];
+
+        Ok(SeekPoint {
+            offset: start.offset as usize,
+            frame_offset: entries[index].offset as usize,
+            discard: target - start.sample,
+            accuracy: Accuracy::Exact,
+        })
+    }
+
+    fn seek_estimated(&mut self, target: u64) -> Result<SeekPoint, ErrorType> {
+        let toc = self.toc.as_ref().ok_or(ErrorType::NoFrames)?;
+        if toc.last().is_none_or(|(total, _)| target >= *total) {
+            return Err(ErrorType::OutOfIndex);
+        }
+
+        let byte = interpolate(toc, target) as usize;
+        let frame = read_frame(&mut self.buffer, byte.max(self.audio_start))
+            .ok_or(ErrorType::OutOfIndex)?;
+
+        let samples_per_frame = self.header.samples_per_frame() as u64;
+        let by_offset: Vec<(u64, u64)> =
+            toc.iter().map(|(sample, byte)| (*byte, *sample)).collect();
+        let frame_sample = interpolate(&by_offset, frame.offset_byte() as u64) / samples_per_frame
+            * samples_per_frame;
+
+        let frame_offset = frame.offset_byte();
+        let margin = frame.side_info().main_data_begin as usize + 3 * frame.length_byte();
+        let mut frames = frames_up_to(
+            &mut self.buffer,
+            frame_offset.saturating_sub(margin).max(self.audio_start),
+            frame_offset,
+        );
+        frames.push(frame);
+
+        let index = frames.len() - 1;
+        let priming = frame::priming_frames(&frames, index);
+
+        Ok(SeekPoint {
+            offset: frames[index - priming].offset_byte(),
+            frame_offset,
+            discard: target.saturating_sub(frame_sample) + priming as u64 * samples_per_frame,
+            accuracy: Accuracy::Estimated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stream::AudioStream;
+
+    const SAMPLE: &str = "mp3-examples/test_data_100kb.mp3";
+
+    #[test]
+    fn test_seek_to_sample() {
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_file(SAMPLE)).unwrap();
+        let frames = stream.frames();
+
+        let mut file =
+            Mp3File::create_from_buffer(Buffer::create_buffer_from_file(SAMPLE)).unwrap();
+        assert_eq!(file.delay(), 576 + DECODER_DELAY);
+        assert_eq!(stream.delay(), file.delay());
+
+        // Without an index the Info frame table of contents gives an estimate.
+        let estimated = file.seek_to_sample(100_000).unwrap();
+        assert_eq!(estimated.accuracy, Accuracy::Estimated);
+
+        file.scan();
+        let start = file.seek_to_sample(0).unwrap();
+        assert_eq!(start.offset, frames[0].offset_byte());
+        assert_eq!(start.discard, 576 + DECODER_DELAY);
+
+        // Sample 100000 is decoded sample 101105, in frame 87.
+        let point = file.seek_to_sample(100_000).unwrap();
+        let priming = frame::priming_frames(frames, 87);
+        assert_eq!(point.accuracy, Accuracy::Exact);
+        assert_eq!(point.frame_offset, frames[87].offset_byte());
+        assert_eq!(point.offset, frames[87 - priming].offset_byte());
+        assert_eq!(point.discard, 101_105 - (87 - priming) as u64 * 1152);
+
+        let frame_index = |offset| {
+            frames
+                .iter()
+                .position(|frame| frame.offset_byte() == offset)
+                .unwrap()
+        };
+        let estimated_index = frame_index(estimated.frame_offset);
+        assert!(estimated_index.abs_diff(87) <= 2);
+        assert_eq!(
+            frame_index(estimated.offset),
+            estimated_index - frame::priming_frames(frames, estimated_index)
+        );
+
+        assert_eq!(file.seek_to_sample(1_000_000), Err(ErrorType::OutOfIndex));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    buffer::{self, Buffer},
+    decoder::{self, LINES, MAX_RESERVOIR},
+    error::ErrorType,
+    gain::GAIN_STEP_DB,
+    side_info::{self, BIG_VALUES_OFFSET, GLOBAL_GAIN_OFFSET},
+    stream::AudioStream,
+};
+
+/// Attenuation a fade ramps to, in dB, before the granules it leads to are muted.
+const FADE_DEPTH_DB: f64 = 60.0;
+
+/// A stretch of audio to mute, in seconds from the start of the decoded audio, after the
+/// encoder delay of the LAME or VBRI header and the decoder delay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MuteRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl MuteRange {
+    /// How far the granule from `start` to `end` is muted, from 0 (untouched) to 1 (muted), with
+    /// `fade` seconds at either end of the range to ramp the level down and back up.
+    fn depth(&self, start: f64, end: f64, fade: f64) -> f64 {
+        if end <= self.start || start >= self.end {
+            return 0.0;
+        }
+        if fade <= 0.0 {
+            return 1.0;
+        }
+
+        let middle = ((start + end) / 2.0).clamp(self.start, self.end);
+        ((middle - self.start).min(self.end - middle) / fade).clamp(0.0, 1.0)
+    }
+}
+
+/// A granule changed by [`mute`].
+#[derive(Debug, PartialEq)]
+pub struct MutedGranule {
+    pub frame: usize,
+    pub granule: usize,
+    /// Start of the granule in seconds, on the timeline of the ranges.
+    pub time: f64,
+    /// `global_gain` of every channel, before and after.
+    pub gains: Vec<(u8, u8)>,
+    /// Whether the coded values were cleared as well.
+    pub cleared: bool,
+}
+
+pub struct MuteEdit {
+    pub data: Vec<u8>,
+    pub granules: Vec<MutedGranule>,
+}
+
+/// Sets the bits from `start` to `end` of the main data whose bytes are at `offsets` in `data`.
+fn fill_ones(data: &mut [u8], offsets: &[usize], start: usize, end: usize) {
+    for bit in start..end {
+        data[offsets[bit / 8]] |= 0x80 >> (bit % 8);
+    }
+}
+
+/// Mutes `ranges` of the audio of `buffer` without re-encoding it, by lowering `global_gain` in
+/// the granules they cover down to 0, which leaves them silent once decoded. With `fade`, the
+/// level ramps down over that many seconds at the start of every range, in steps of 1.5dB, and
+/// back up at its end. With `clear`, the coded values of the muted granules are replaced by
+/// zeros as well (`big_values` set to 0 and every count1 quadruple to zero), so nothing of the
+/// original is left in them. Bit lengths do not change, so the bit reservoir and every other
+/// granule stay as they were; CRCs are recomputed. Every granule changed is reported.
+pub fn mute(
+    buffer: &mut Buffer,
+    ranges: &[MuteRange],
+    fade: f64,
+    clear: bool,
+) -> Result<MuteEdit, ErrorType> {
+    let stream = AudioStream::create_from_buffer(buffer.share())?;
+    let header = stream.header();
+    let frequency = header.get_frequency()? as f64;
+    let delay = stream.delay() as f64;
+    let granule_count = header.granule_count();
+
+    let mut data = buffer.data.to_vec();
+    let mut granules = Vec::new();
+    // File offsets of the main data bytes the next frame can point back to.
+    let mut reservoir: Vec<usize> = Vec::new();
+
+    for (index, frame) in stream.frames().iter().enumerate() {
+        let header = frame.header();
+        let start = frame.offset_byte();
+        let end = start + frame.length_byte();
+        let side_info = 8 * (start + 4 + frame.crc().map_or(0, |_| 2));
+
+        let begin = frame.side_info().main_data_begin as usize;
+        let available = reservoir.len();
+        reservoir.extend(end - frame.main_data_length()..end);
+        let main_data = (begin <= available).then(|| &reservoir[available - begin..]);
+        // Bits of the scale factors, which clearing keeps.
+        let part2 = match main_data {
+            Some(offsets) if clear => {
+                let bytes: Vec<u8> = offsets.iter().map(|&offset| buffer.data[offset]).collect();
+                Some(decoder::part2_lengths(frame, &bytes))
+            }
+            _ => None,
+        };
+
+        let mut changed = false;
+        let mut bit = 0;
+        for (gr, granule) in frame.side_info().granules[..granule_count]
+            .iter()
+            .enumerate()
+        {
+            let sample = ((index * granule_count + gr) * LINES) as f64;
+            let time = (sample - delay) / frequency;
+            let depth = ranges
+                .iter()
+                .map(|range| range.depth(time, time + LINES as f64 / frequency, fade))
+                .fold(0.0, f64::max);
+            let granule_bits = granule
+                .channels()
+                .iter()
+                .map(|channel| channel.part_23_length as usize);
+            let granule_start = bit;
+            bit += granule_bits.sum::<usize>();
+            if depth == 0.0 {
+                continue;
+            }
+
+            let steps = (depth * FADE_DEPTH_DB / GAIN_STEP_DB).round() as u8;
+            let muted = depth >= 1.0;
+            // Cleared only when the granule can be found in the bit reservoir, and its bits do
+            // not run past the main data of a corrupt frame.
+            let clear_with = match (part2, main_data) {
+                (Some(part2), Some(offsets)) if muted && bit <= 8 * offsets.len() => {
+                    Some((part2, offsets))
+                }
+                _ => None,
+            };
+            let cleared = clear_with.is_some();
+            let mut gains = Vec::new();
+            let mut channel_start = granule_start;
+
+            for (ch, channel) in granule.channels().iter().enumerate() {
+                let pos = side_info + side_info::channel_info_offset(header, gr, ch);
+                let gain = if muted {
+                    0
+                } else {
+                    channel.global_gain.saturating_sub(steps)
+                };
+                gains.push((channel.global_gain, gain));
+                buffer::write_bits(&mut data, pos + GLOBAL_GAIN_OFFSET, 8, gain as u32);
+
+                if let Some((part2, offsets)) = clear_with {
+                    // Count1 quadruples from the first line, each a single 1 bit in table A
+                    // for four zeros, up to the end of the granule.
+                    let channel_end = channel_start + channel.part_23_length as usize;
+                    fill_ones(
+                        &mut data,
+                        offsets,
+                        channel_start + part2[gr][ch],
+                        channel_end,
+                    );
+
+                    buffer::write_bits(&mut data, pos + BIG_VALUES_OFFSET, 9, 0);
+                    let count1_table_select = pos + side_info::channel_info_length(header) - 1;
+                    buffer::write_bits(&mut data, count1_table_select, 1, 0);
+                }
+                channel_start += channel.part_23_length as usize;
+            }
+
+            changed = true;
+            granules.push(MutedGranule {
+                frame: index,
+                granule: gr,
+                time,
+                gains,
+                cleared,
+            });
+        }
+
+        if changed && frame.crc().is_some() {
+            let crc = frame.compute_crc(&data[start..end]);
+            data[start + 4..start + 6].copy_from_slice(&crc.to_be_bytes());
+        }
+
+        let excess = reservoir.len().saturating_sub(MAX_RESERVOIR);
+        reservoir.drain(..excess);
+    }
+
+    stream.update_music_crc(&mut data);
+
+    Ok(MuteEdit { data, granules })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{decoder::Decoder, side_info::PART_23_LENGTH_OFFSET, validate};
+
+    #[test]
+    fn test_mute() {
+        let original = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3")
+            .data
+            .to_vec();
+        let ranges = [MuteRange {
+            start: 1.0,
+            end: 2.0,
+        }];
+
+        for clear in [false, true] {
+            let edit = mute(
+                &mut Buffer::create_buffer_from_data(original.clone()),
+                &ranges,
+                0.0,
+                clear,
+            )
+            .unwrap();
+            // About 1s of 13ms granules, one more for the overlap at either end.
+            assert!((77..=79).contains(&edit.granules.len()));
+            assert!(edit.granules.iter().all(|granule| granule.cleared == clear));
+            assert!(edit
+                .granules
+                .iter()
+                .flat_map(|granule| &granule.gains)
+                .all(|&(_, after)| after == 0));
+            assert_eq!(edit.data.len(), original.len());
+            assert!(
+                validate::validate(&mut Buffer::create_buffer_from_slice(&edit.data)).is_empty()
+            );
+
+            let stream =
+                AudioStream::create_from_buffer(Buffer::create_buffer_from_slice(&edit.data))
+                    .unwrap();
+            let mut decoder = Decoder::new();
+            let muted: Vec<usize> = edit.granules.iter().map(|granule| granule.frame).collect();
+            for (index, frame) in stream.frames().iter().enumerate() {
+                let Ok(spectra) = decoder.decode_spectra(frame, stream.frame_data(frame)) else {
+                    continue;
+                };
+                let silent = spectra
+                    .iter()
+                    .flatten()
+                    .all(|spectrum| spectrum.lines.iter().all(|&line| line.abs() < 1e-9));
+                if muted.contains(&index) && muted.iter().filter(|&&i| i == index).count() == 2 {
+                    assert!(silent, "{index}");
+                    // Cleared granules code nothing but zeros.
+                    let exact = spectra
+                        .iter()
+                        .flatten()
+                        .all(|spectrum| spectrum.lines.iter().all(|&line| line == 0.0));
+                    assert_eq!(exact, clear);
+                } else if !muted.contains(&index) {
+                    assert!(!silent, "{index}");
+                }
+            }
+        }
+
+        // A fade lowers the level step by step before muting.
+        let edit = mute(
+            &mut Buffer::create_buffer_from_data(original.clone()),
+            &ranges,
+            0.2,
+            false,
+        )
+        .unwrap();
+        let drops: Vec<u8> = edit
+            .granules
+            .iter()
+            .map(|granule| granule.gains[0].0 - granule.gains[0].1)
+            .collect();
+        assert!(drops[0] < drops[5] && drops[5] < drops[20]);
+        assert!(edit.granules[30].gains.iter().all(|&(_, after)| after == 0));
+
+        // A granule claiming more bits than the main data holds is muted but not cleared.
+        let mut data = original.clone();
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_slice(&original)).unwrap();
+        let frame = &stream.frames()[60];
+        let side_info = 8 * (frame.offset_byte() + 4 + frame.crc().map_or(0, |_| 2));
+        for (gr, ch) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let pos = side_info
+                + side_info::channel_info_offset(frame.header(), gr, ch)
+                + PART_23_LENGTH_OFFSET;
+            buffer::write_bits(&mut data, pos, 12, 4095);
+        }
+        let edit = mute(
+            &mut Buffer::create_buffer_from_data(data),
+            &ranges,
+            0.0,
+            true,
+        )
+        .unwrap();
+        let corrupt: Vec<&MutedGranule> = edit
+            .granules
+            .iter()
+            .filter(|granule| !granule.cleared)
+            .collect();
+        assert_eq!(corrupt.len(), 1);
+        assert_eq!((corrupt[0].frame, corrupt[0].granule), (60, 1));
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{buffer::Buffer, frame::Frame, header::Header, tag};
+
+/// Files are not cut into chunks smaller than this.
+const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+/// Number of threads to use when none is given: one per available CPU.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Frames starting in `start..end`, and the first frame after them.
+struct Chunk {
+    end: usize,
+    frames: Vec<Frame>,
+    next: Option<Frame>,
+}
+
+/// Whether a valid header follows `frame`, or the data ends right after it.
+fn is_confirmed(buffer: &mut Buffer, frame: &Frame) -> bool {
+    let end = frame.offset_byte() + frame.length_byte();
+    if end + 4 > buffer.data.len() {
+        return true;
+    }
+
+    buffer.pos = end * 8;
+    Header::create_from_buffer(buffer).is_ok_and(|header| header.validate_header().is_ok())
+}
+
+/// Scans the frames starting in `start..end`. Except for the first chunk, whose start is where
+/// a sequential scan starts, the first frame has to be followed by another header so that the
+/// scan rarely locks onto a false sync inside audio data.
+fn scan_chunk(mut buffer: Buffer, start: usize, end: usize, is_first: bool) -> Chunk {
+    let mut frames = Vec::new();
+    buffer.pos = start * 8;
+
+    let next = loop {
+        let Some(frame) = buffer.next_frame() else {
+            break None;
+        };
+        if frame.offset_byte() >= end {
+            break Some(frame);
+        }
+
+        if frames.is_empty() && !is_first && !is_confirmed(&mut buffer, &frame) {
+            buffer.pos = (frame.offset_byte() + 1) * 8;
+            continue;
+        }
+        buffer.pos = (frame.offset_byte() + frame.length_byte()) * 8;
+
+        frames.push(frame);
+    };
+
+    Chunk { end, frames, next }
+}
+
+/// Same frames as [`Buffer::extract_frames`] (from the start of the data), scanned in `chunks`
+/// parts in parallel.
+///
+/// Every chunk is scanned from its own start, so its first frames can differ from the sequential
+/// scan when a chunk boundary falls in junk or it locks onto a false sync. The chunks are stitched
+/// by following the frames of the previous chunk into the next one until they reach a frame that
+/// the next chunk also found; from there on both scans are the same.
+pub fn extract_frames_in_chunks(buffer: &mut Buffer, chunks: usize) -> Vec<Frame> {
+    let audio_start = tag::id3v2_length(&buffer.data);
+    let length = buffer.data.len().saturating_sub(audio_start);
+    let chunks = chunks.clamp(1, length.max(1));
+
+    let bounds: Vec<usize> = (0..=chunks)
+        .map(|i| audio_start + length * i / chunks)
+        .collect();
+
+    let mut results: Vec<Chunk> = thread::scope(|scope| {
+        let workers: Vec<_> = bounds
+            .windows(2)
+            .enumerate()
+            .map(|(i, range)| {
+                let shared = buffer.share();
+                let (start, end) = (range[0], range[1]);
+                scope.spawn(move || scan_chunk(shared, start, end, i == 0))
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Scanning thread panicked"))
+            .collect()
+    });
+
+    let first = results.remove(0);
+    let mut frames = first.frames;
+    let mut next = first.next;
+
+    for chunk in results {
+        while let Some(frame) = next.take() {
+            if frame.offset_byte() >= chunk.end {
+                next = Some(frame);
+                break;
+            }
+
+            let found = chunk
+                .frames
+                .binary_search_by_key(&frame.offset_byte(), Frame::offset_byte);
+            if let Ok(index) = found {
+                frames.extend(chunk.frames.into_iter().skip(index));
+                next = chunk.next;
+                break;
+            }
+
+            // The chunk started differently: continue the sequential scan into it.
+            buffer.pos = (frame.offset_byte() + frame.length_byte()) * 8;
+            frames.push(frame);
+            next = buffer.next_frame();
+        }
+    }
+
+    frames
+}
+
+/// [`extract_frames_in_chunks`] with one chunk per thread, at most, and chunks of at least 1MiB.
+pub fn extract_frames(buffer: &mut Buffer, threads: usize) -> Vec<Frame> {
+    let chunks = threads.min(buffer.data.len() / MIN_CHUNK_SIZE);
+    if chunks <= 1 {
+        buffer.pos = 0;
+        return buffer.extract_frames();
+    }
+
+    extract_frames_in_chunks(buffer, chunks)
+}
+
+/// Runs `process` on every file of `paths` on `threads` threads and returns the results in the
+/// order of `paths`.
+pub fn process_files<T, F>(paths: &[PathBuf], threads: usize, process: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let threads = threads.clamp(1, paths.len().max(1));
+
+    let mut results: Vec<(usize, T)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(i) else {
+                            break results;
+                        };
+                        results.push((i, process(path)));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Processing thread panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract_frames_in_chunks() {
+        let data = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3")
+            .data
+            .to_vec();
+
+        // Junk in front and in the middle, so chunk starts and stitching are exercised.
+        let mut junked = vec![0xff; 3000];
+        junked.extend_from_slice(&data[..40_000]);
+        junked.extend(std::iter::repeat_n(0xffu8, 777));
+        junked.extend_from_slice(&data[40_000..]);
+
+        for data in [data, junked] {
+            let mut buffer = Buffer::create_buffer_from_data(data);
+            let expected: Vec<usize> = buffer
+                .extract_frames()
+                .iter()
+                .map(Frame::offset_byte)
+                .collect();
+
+            for chunks in [1, 2, 7, 31] {
+                let frames = extract_frames_in_chunks(&mut buffer, chunks);
+                let offsets: Vec<usize> = frames.iter().map(Frame::offset_byte).collect();
+                assert_eq!(offsets, expected, "{chunks} chunks");
+            }
+        }
+    }
+}
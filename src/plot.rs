@@ -0,0 +1,319 @@
+use std::fmt::Write;
+
+use crate::frame::Frame;
+
+const WIDTH: f64 = 1200.0;
+const LEFT: f64 = 70.0;
+const RIGHT: f64 = 20.0;
+const TITLE_HEIGHT: f64 = 40.0;
+const PANEL_HEIGHT: f64 = 160.0;
+/// Space under every panel for its time axis.
+const PANEL_GAP: f64 = 50.0;
+/// Height of one channel row of the block type strip.
+const STRIP_ROW: f64 = 14.0;
+
+const CHANNEL_COLORS: [&str; 2] = ["#1f77b4", "#ff7f0e"];
+/// Colours of start, short, mixed and stop blocks.
+const BLOCK_COLORS: [(&str, &str); 4] = [
+    ("start", "#2ca02c"),
+    ("short", "#d62728"),
+    ("mixed", "#9467bd"),
+    ("stop", "#8c564b"),
+];
+
+/// A polyline of `(seconds, value)` points.
+struct Series {
+    label: String,
+    color: &'static str,
+    points: Vec<(f64, f64)>,
+}
+
+/// Step between axis ticks giving about `ticks` ticks up to `max`: 1, 2 or 5 times a power of 10.
+fn tick_step(max: f64, ticks: f64) -> f64 {
+    let raw = (max / ticks).max(f64::MIN_POSITIVE);
+    let power = 10f64.powf(raw.log10().floor());
+
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * power)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * power)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Maps seconds to x coordinates.
+struct TimeAxis {
+    duration: f64,
+}
+
+impl TimeAxis {
+    fn x(&self, seconds: f64) -> f64 {
+        LEFT + seconds / self.duration.max(f64::MIN_POSITIVE) * (WIDTH - LEFT - RIGHT)
+    }
+
+    /// Tick marks and labels under a band ending at `bottom`.
+    fn write(&self, svg: &mut String, bottom: f64) {
+        let step = tick_step(self.duration, 10.0);
+        let mut seconds = 0.0;
+        while seconds <= self.duration + step * 1e-9 {
+            let x = self.x(seconds);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{x:.1}" y1="{bottom}" x2="{x:.1}" y2="{}" stroke="#444"/><text x="{x:.1}" y="{}" text-anchor="middle">{}</text>"##,
+                bottom + 5.0,
+                bottom + 18.0,
+                format_tick(seconds, step),
+            );
+            seconds += step;
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">seconds</text>"#,
+            WIDTH - RIGHT,
+            bottom + 34.0,
+        );
+    }
+}
+
+fn format_tick(value: f64, step: f64) -> String {
+    if step >= 1.0 {
+        format!("{value:.0}")
+    } else {
+        let decimals = (-step.log10()).ceil() as usize;
+        format!("{value:.decimals$}")
+    }
+}
+
+/// Draws `series` in a panel whose top is at `top`.
+fn write_panel(svg: &mut String, axis: &TimeAxis, top: f64, title: &str, series: &[Series]) {
+    let bottom = top + PANEL_HEIGHT;
+    let max = series
+        .iter()
+        .flat_map(|series| series.points.iter().map(|point| point.1))
+        .fold(0.0, f64::max)
+        .max(1.0);
+    let step = tick_step(max, 4.0);
+    let max = (max / step).ceil() * step;
+    let y = |value: f64| bottom - value / max * PANEL_HEIGHT;
+
+    let _ = writeln!(
+        svg,
+        r##"<text x="{LEFT}" y="{}" font-weight="bold">{}</text>"##,
+        top - 6.0,
+        escape(title)
+    );
+    let mut value = 0.0;
+    while value <= max + step * 1e-9 {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{LEFT}" y1="{0:.1}" x2="{1}" y2="{0:.1}" stroke="#ddd"/><text x="{2}" y="{3:.1}" text-anchor="end">{4}</text>"##,
+            y(value),
+            WIDTH - RIGHT,
+            LEFT - 6.0,
+            y(value) + 4.0,
+            format_tick(value, step),
+        );
+        value += step;
+    }
+
+    for (i, series) in series.iter().enumerate() {
+        let points: Vec<String> = series
+            .points
+            .iter()
+            .map(|(seconds, value)| format!("{:.2},{:.2}", axis.x(*seconds), y(*value)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1" points="{}"/>"#,
+            series.color,
+            points.join(" ")
+        );
+        if !series.label.is_empty() {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" text-anchor="end" fill="{}">{}</text>"#,
+                WIDTH - RIGHT - i as f64 * 90.0,
+                top - 6.0,
+                series.color,
+                escape(&series.label)
+            );
+        }
+    }
+
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{LEFT}" y="{top}" width="{}" height="{PANEL_HEIGHT}" fill="none" stroke="#444"/>"##,
+        WIDTH - LEFT - RIGHT
+    );
+    axis.write(svg, bottom);
+}
+
+/// Renders per-frame bitrate, bit reservoir use (`main_data_begin`), `part_23_length` per
+/// granule and channel, and the granules using other than long blocks, over time, as a standalone
+/// SVG document titled `title`.
+pub fn plot(frames: &[Frame], title: &str) -> String {
+    let channels = frames
+        .iter()
+        .map(|frame| frame.side_info().granules[0].channel_count)
+        .max()
+        .unwrap_or(1);
+
+    let mut bitrate = Series {
+        label: String::new(),
+        color: CHANNEL_COLORS[0],
+        points: Vec::new(),
+    };
+    let mut reservoir = Series {
+        label: String::new(),
+        color: CHANNEL_COLORS[0],
+        points: Vec::new(),
+    };
+    let mut part_23: Vec<Series> = (0..channels)
+        .map(|ch| Series {
+            label: format!("channel {ch}"),
+            color: CHANNEL_COLORS[ch],
+            points: Vec::new(),
+        })
+        .collect();
+    // (start, end, channel, block colour) of every granule that is not long blocks only.
+    let mut blocks = Vec::new();
+
+    let mut seconds = 0.0;
+    for frame in frames {
+        let header = frame.header();
+        let frequency = header.get_frequency().unwrap_or(44100) as f64;
+        let length = header.samples_per_frame() as f64 / frequency;
+        let kbps = header.get_bitrate().unwrap_or(0) as f64;
+
+        bitrate.points.push((seconds, kbps));
+        bitrate.points.push((seconds + length, kbps));
+        reservoir
+            .points
+            .push((seconds, frame.side_info().main_data_begin as f64));
+
+        let granules = header.granule_count();
+        let granule_length = length / granules as f64;
+        for (gr, granule) in frame.side_info().granules[..granules].iter().enumerate() {
+            let start = seconds + gr as f64 * granule_length;
+            for (ch, channel) in granule.channels().iter().enumerate() {
+                part_23[ch]
+                    .points
+                    .push((start, channel.part_23_length as f64));
+
+                if channel.windows_switching {
+                    let kind = match channel.block_type {
+                        1 => 0,
+                        2 if channel.mixed_block_flag => 2,
+                        2 => 1,
+                        _ => 3,
+                    };
+                    blocks.push((start, start + granule_length, ch, BLOCK_COLORS[kind].1));
+                }
+            }
+        }
+
+        seconds += length;
+    }
+
+    let axis = TimeAxis { duration: seconds };
+    let strip_top = TITLE_HEIGHT + 3.0 * (PANEL_HEIGHT + PANEL_GAP) + 20.0;
+    let height = strip_top + channels as f64 * STRIP_ROW + PANEL_GAP;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="sans-serif" font-size="11">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="white"/><text x="{LEFT}" y="22" font-size="16" font-weight="bold">{}</text>"#,
+        escape(title)
+    );
+
+    let panels = [
+        ("Bitrate (kb/s)", vec![bitrate]),
+        ("Bit reservoir: main_data_begin (bytes)", vec![reservoir]),
+        ("part_23_length per granule (bits)", part_23),
+    ];
+    for (i, (title, series)) in panels.iter().enumerate() {
+        let top = TITLE_HEIGHT + 20.0 + i as f64 * (PANEL_HEIGHT + PANEL_GAP);
+        write_panel(&mut svg, &axis, top, title, series);
+    }
+
+    let _ = writeln!(
+        svg,
+        r##"<text x="{LEFT}" y="{}" font-weight="bold">Block types</text>"##,
+        strip_top - 6.0
+    );
+    for (i, (label, color)) in BLOCK_COLORS.iter().enumerate() {
+        let x = WIDTH - RIGHT - (BLOCK_COLORS.len() - i) as f64 * 60.0;
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{x}" y="{}" width="10" height="10" fill="{color}"/><text x="{}" y="{}">{label}</text>"#,
+            strip_top - 16.0,
+            x + 14.0,
+            strip_top - 7.0,
+        );
+    }
+    for ch in 0..channels {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">ch {ch}</text>"#,
+            LEFT - 6.0,
+            strip_top + (ch as f64 + 0.8) * STRIP_ROW,
+        );
+    }
+    for (start, end, ch, color) in &blocks {
+        let x = axis.x(*start);
+        let _ = writeln!(
+            svg,
+            r#"<rect class="block" x="{x:.2}" y="{}" width="{:.2}" height="{}" fill="{color}"/>"#,
+            strip_top + *ch as f64 * STRIP_ROW,
+            (axis.x(*end) - x).max(1.0),
+            STRIP_ROW - 2.0,
+        );
+    }
+    axis.write(&mut svg, strip_top + channels as f64 * STRIP_ROW);
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer::Buffer, stream::AudioStream};
+
+    #[test]
+    fn test_plot() {
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+        let svg = plot(stream.frames(), "a <b> & c");
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("a &lt;b&gt; &amp; c"));
+        // Bitrate, reservoir and one part_23_length line per channel.
+        assert_eq!(svg.matches("<polyline").count(), 4);
+
+        let switching = stream
+            .frames()
+            .iter()
+            .flat_map(|frame| frame.side_info().granules.iter())
+            .flat_map(|granule| granule.channels())
+            .filter(|channel| channel.windows_switching)
+            .count();
+        assert!(switching > 0);
+        assert_eq!(svg.matches(r#"class="block""#).count(), switching);
+
+        assert_eq!(tick_step(4.02, 10.0), 0.5);
+        assert_eq!(tick_step(320.0, 4.0), 100.0);
+    }
+}
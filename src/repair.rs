@@ -0,0 +1,119 @@
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    xing::{VbriHeader, XingHeader},
+};
+
+pub struct Repair {
+    pub data: Vec<u8>,
+    pub previous: Option<XingHeader>,
+    /// The VBRI header the new Xing/Info frame replaced.
+    pub previous_vbri: Option<VbriHeader>,
+    pub current: XingHeader,
+}
+
+/// Rescans the frames of `buffer` and writes a Xing/Info frame that matches them, replacing the
+/// existing Xing/Info or VBRI frame or inserting a new one before the first audio frame.
+///
+/// Everything else (tags, audio frames and any junk between them) is copied byte for byte. The
+/// quality and LAME fields of the previous header are kept, with its music length and CRC
+/// recomputed.
+pub fn repair(buffer: &mut Buffer) -> Result<Repair, ErrorType> {
+    let mut frames = buffer.extract_frames();
+    let first = frames.first().ok_or(ErrorType::NoFrames)?;
+
+    let prefix_end = first.offset_byte();
+    let previous = XingHeader::parse(buffer.frame_data(first), first.header());
+    let previous_vbri = previous
+        .is_none()
+        .then(|| VbriHeader::parse(buffer.frame_data(first)))
+        .flatten();
+    if previous.is_some() || previous_vbri.is_some() {
+        frames.remove(0);
+    }
+
+    let (first, last) = match (frames.first(), frames.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(ErrorType::NoFrames),
+    };
+    let audio_start = first.offset_byte();
+    let body = &buffer.data[audio_start..last.offset_byte() + last.length_byte()];
+
+    let frame_offsets: Vec<usize> = frames
+        .iter()
+        .map(|frame| frame.offset_byte() - audio_start)
+        .collect();
+    let current = XingHeader::describe(body, &frame_offsets, first.header(), previous.as_ref())?;
+
+    let mut data = buffer.data[..prefix_end].to_vec();
+    data.extend(current.to_frame(first.header(), body)?);
+    data.extend_from_slice(&buffer.data[audio_start..]);
+
+    Ok(Repair {
+        data,
+        previous,
+        previous_vbri,
+        current,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{mp3_file::DECODER_DELAY, stream::AudioStream};
+
+    #[test]
+    fn test_repair_truncated_file() {
+        let mut data = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3")
+            .data
+            .to_vec();
+        data.truncate(data.len() / 2);
+
+        let repair = repair(&mut Buffer::create_buffer_from_data(data)).unwrap();
+        assert_eq!(repair.previous.unwrap().frames, Some(153));
+
+        let mut buffer = Buffer::create_buffer_from_data(repair.data);
+        let frames = buffer.extract_frames();
+        let xing = XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+
+        assert_eq!(xing, repair.current);
+        assert_eq!(xing.frames, Some(frames.len() as u32 - 1));
+
+        let last = frames.last().unwrap();
+        assert_eq!(
+            xing.bytes,
+            Some((last.offset_byte() + last.length_byte()) as u32)
+        );
+    }
+
+    #[test]
+    fn test_repair_replaces_vbri() {
+        // The Info frame of the sample turned into a VBRI frame.
+        let mut data = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3")
+            .data
+            .to_vec();
+        data[4..417].fill(0);
+        data[36..40].copy_from_slice(b"VBRI");
+        data[40..42].copy_from_slice(&1u16.to_be_bytes());
+        data[42..44].copy_from_slice(&576u16.to_be_bytes());
+        // No table of contents, with entries of a byte.
+        data[56..58].copy_from_slice(&1u16.to_be_bytes());
+        data[58..60].copy_from_slice(&1u16.to_be_bytes());
+
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(data.clone())).unwrap();
+        assert_eq!(stream.frames().len(), 153);
+        assert_eq!(stream.vbri().unwrap().delay, 576);
+        assert_eq!(stream.delay(), 576 + DECODER_DELAY);
+
+        let repair = repair(&mut Buffer::create_buffer_from_data(data)).unwrap();
+        assert!(repair.previous.is_none());
+        assert_eq!(repair.previous_vbri.unwrap().delay, 576);
+        assert_eq!(repair.current.frames, Some(153));
+
+        let mut buffer = Buffer::create_buffer_from_data(repair.data);
+        let frames = buffer.extract_frames();
+        assert_eq!(frames.len(), 1 + 153);
+        assert!(VbriHeader::parse(buffer.frame_data(&frames[1])).is_none());
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    frame::{self, FrameRecord},
+    header::{Mode, Version},
+};
+
+/// Header-level facts about one file, from [`Buffer::scan_headers`]. A leading Xing/Info or
+/// VBRI frame is not counted as audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub frames: usize,
+    /// In seconds.
+    pub duration: f64,
+    /// Average over the audio frames in kb/sec, from their size.
+    pub bitrate: f64,
+    /// Whether the frames have different bitrates.
+    pub vbr: bool,
+    /// Version, sample rate and mode of the first audio frame.
+    pub version: Version,
+    pub frequency: u16,
+    pub mode: Mode,
+    /// Frames per bitrate in kb/sec, 0 for free format.
+    pub bitrates: BTreeMap<u16, usize>,
+}
+
+/// Result for one file of a batch.
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub result: Result<FileInfo, String>,
+}
+
+/// Totals over the files of a batch. Files with errors only count in `files` and `errors`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub files: usize,
+    pub errors: usize,
+    pub frames: usize,
+    pub duration: f64,
+    pub vbr_files: usize,
+    /// Frames per bitrate over all files.
+    pub bitrates: BTreeMap<u16, usize>,
+    /// Files per sample rate, version and mode.
+    pub frequencies: BTreeMap<u16, usize>,
+    pub versions: BTreeMap<String, usize>,
+    pub modes: BTreeMap<String, usize>,
+}
+
+impl FileInfo {
+    /// Scans the frame headers of `buffer` from its start.
+    pub fn scan(buffer: &mut Buffer) -> Result<Self, ErrorType> {
+        buffer.pos = 0;
+        Self::create_from_records(&buffer.scan_audio_headers())
+    }
+
+    pub fn create_from_records(records: &[FrameRecord]) -> Result<Self, ErrorType> {
+        let first = records.first().ok_or(ErrorType::NoFrames)?;
+
+        let duration = frame::duration(records);
+        let bytes: usize = records.iter().map(|record| record.length).sum();
+        let mut bitrates = BTreeMap::new();
+        for record in records {
+            *bitrates.entry(record.bitrate).or_insert(0) += 1;
+        }
+
+        Ok(Self {
+            frames: records.len(),
+            duration,
+            bitrate: if duration > 0.0 {
+                bytes as f64 * 8.0 / duration / 1000.0
+            } else {
+                0.0
+            },
+            vbr: bitrates.len() > 1,
+            version: first.version,
+            frequency: first.frequency,
+            mode: first.mode,
+            bitrates,
+        })
+    }
+}
+
+impl Summary {
+    pub fn create_from_reports(reports: &[FileReport]) -> Self {
+        let mut summary = Self {
+            files: reports.len(),
+            ..Self::default()
+        };
+
+        for report in reports {
+            let Ok(info) = &report.result else {
+                summary.errors += 1;
+                continue;
+            };
+
+            summary.frames += info.frames;
+            summary.duration += info.duration;
+            summary.vbr_files += info.vbr as usize;
+            for (bitrate, frames) in &info.bitrates {
+                *summary.bitrates.entry(*bitrate).or_insert(0) += frames;
+            }
+            *summary.frequencies.entry(info.frequency).or_insert(0) += 1;
+            *summary
+                .versions
+                .entry(info.version.to_string())
+                .or_insert(0) += 1;
+            *summary.modes.entry(info.mode.to_string()).or_insert(0) += 1;
+        }
+
+        summary
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters and `?` for
+/// any single character.
+fn matches_name(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where the pattern goes on after the last `*`, and where the name goes on after it.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // Let the last `*` cover one more character and try again.
+            _ => match backtrack {
+                Some((after_star, covered)) => {
+                    p = after_star;
+                    n = covered + 1;
+                    backtrack = Some((after_star, n));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether the components of a path match those of a pattern, where a `**` component stands for
+/// any number of directories.
+fn matches_path(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (None, _) => path.is_empty(),
+        (Some((p, rest)), _) if *p == ['*', '*'] => {
+            matches_path(rest, path) || (!path.is_empty() && matches_path(pattern, &path[1..]))
+        }
+        (Some((p, rest)), Some((c, path))) => matches_name(p, c) && matches_path(rest, path),
+        (Some(_), None) => false,
+    }
+}
+
+fn components(path: &Path) -> Vec<Vec<char>> {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy().chars().collect())
+        .collect()
+}
+
+/// Every file under `dir`, sorted by path, and the directories that cannot be read. Symbolic
+/// links to directories are not followed, so that links back up the tree do not loop.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>, failed: &mut Vec<FileReport>) {
+    let entries = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.path(), entry.file_type()?.is_dir()))
+            })
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => {
+            failed.push(FileReport {
+                path: dir.to_path_buf(),
+                result: Err(err.to_string()),
+            });
+            return;
+        }
+    };
+    entries.sort();
+
+    for (path, is_dir) in entries {
+        if is_dir {
+            walk(&path, files, failed);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn is_mp3(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"))
+}
+
+/// Files named by `inputs`: files as they are, every `.mp3` file under directories, and the
+/// files matching patterns with `*`, `?` or `**` components. Directories that cannot be read,
+/// and patterns whose directory does not exist, are returned as failed reports.
+pub fn collect_files(inputs: &[String]) -> (Vec<PathBuf>, Vec<FileReport>) {
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        let mut found = Vec::new();
+
+        if !input.contains(['*', '?']) {
+            if path.is_dir() {
+                walk(path, &mut found, &mut failed);
+                files.extend(found.into_iter().filter(|file| is_mp3(file)));
+            } else {
+                files.push(path.to_path_buf());
+            }
+            continue;
+        }
+
+        // Walk from the directory before the first wildcard.
+        let base: PathBuf = path
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?']))
+            .collect();
+        let pattern = components(path);
+
+        let relative = base.as_os_str().is_empty();
+        let root = if relative { Path::new(".") } else { &base };
+        if !root.is_dir() {
+            failed.push(FileReport {
+                path: path.to_path_buf(),
+                result: Err(format!("`{}` is not a directory", root.display())),
+            });
+            continue;
+        }
+
+        walk(root, &mut found, &mut failed);
+        files.extend(found.into_iter().filter(|file| {
+            let file = if relative {
+                file.strip_prefix(".").unwrap_or(file)
+            } else {
+                file
+            };
+            matches_path(&pattern, &components(file))
+        }));
+    }
+
+    (files, failed)
+}
+
+/// Quotes `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Quotes `value` as a CSV field when it needs it.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_map<K: ToString>(map: &BTreeMap<K, usize>) -> String {
+    let entries: Vec<String> = map
+        .iter()
+        .map(|(key, count)| format!("{}: {count}", json_string(&key.to_string())))
+        .collect();
+
+    format!("{{{}}}", entries.join(", "))
+}
+
+fn table_map<K: ToString>(map: &BTreeMap<K, usize>) -> String {
+    let entries: Vec<String> = map
+        .iter()
+        .map(|(key, count)| format!("{}: {count}", key.to_string()))
+        .collect();
+
+    entries.join(", ")
+}
+
+pub fn write_table(
+    out: &mut impl Write,
+    reports: &[FileReport],
+    summary: &Summary,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>6} {:>9} {:>8} {:>4} {:>6} {:<8} {:<14} File",
+        "Frames", "Duration", "kb/sec", "VBR", "Hz", "Version", "Mode"
+    )?;
+
+    for report in reports {
+        let path = report.path.display();
+        match &report.result {
+            Ok(info) => writeln!(
+                out,
+                "{:>6} {:>9.3} {:>8.1} {:>4} {:>6} {:<8} {:<14} {path}",
+                info.frames,
+                info.duration,
+                info.bitrate,
+                if info.vbr { "yes" } else { "no" },
+                info.frequency,
+                info.version.to_string(),
+                info.mode.to_string(),
+            )?,
+            Err(err) => writeln!(out, "{:>6} {err:<55} {path}", "error")?,
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Files: {} ({} with errors, {} VBR)",
+        summary.files, summary.errors, summary.vbr_files
+    )?;
+    writeln!(out, "Frames: {}", summary.frames)?;
+    writeln!(out, "Total duration: {:.3}s", summary.duration)?;
+    writeln!(out, "Bitrates (frames): {}", table_map(&summary.bitrates))?;
+    writeln!(out, "Sample rates: {}", table_map(&summary.frequencies))?;
+    writeln!(out, "Versions: {}", table_map(&summary.versions))?;
+    writeln!(out, "Modes: {}", table_map(&summary.modes))
+}
+
+pub fn write_json(
+    out: &mut impl Write,
+    reports: &[FileReport],
+    summary: &Summary,
+) -> io::Result<()> {
+    writeln!(out, "{{\n  \"files\": [")?;
+
+    for (i, report) in reports.iter().enumerate() {
+        let path = json_string(&report.path.to_string_lossy());
+        let fields = match &report.result {
+            Ok(info) => format!(
+                "\"frames\": {}, \"duration\": {:.3}, \"bitrate\": {:.1}, \"vbr\": {}, \
+                 \"frequency\": {}, \"version\": {}, \"mode\": {}, \"bitrates\": {}",
+                info.frames,
+                info.duration,
+                info.bitrate,
+                info.vbr,
+                info.frequency,
+                json_string(&info.version.to_string()),
+                json_string(&info.mode.to_string()),
+                json_map(&info.bitrates),
+            ),
+            Err(err) => format!("\"error\": {}", json_string(err)),
+        };
+        let separator = if i + 1 < reports.len() { "," } else { "" };
+        writeln!(out, "    {{\"path\": {path}, {fields}}}{separator}")?;
+    }
+
+    writeln!(out, "  ],\n  \"summary\": {{")?;
+    writeln!(out, "    \"files\": {},", summary.files)?;
+    writeln!(out, "    \"errors\": {},", summary.errors)?;
+    writeln!(out, "    \"vbr_files\": {},", summary.vbr_files)?;
+    writeln!(out, "    \"frames\": {},", summary.frames)?;
+    writeln!(out, "    \"duration\": {:.3},", summary.duration)?;
+    writeln!(out, "    \"bitrates\": {},", json_map(&summary.bitrates))?;
+    writeln!(
+        out,
+        "    \"frequencies\": {},",
+        json_map(&summary.frequencies)
+    )?;
+    writeln!(out, "    \"versions\": {},", json_map(&summary.versions))?;
+    writeln!(out, "    \"modes\": {}", json_map(&summary.modes))?;
+    writeln!(out, "  }}\n}}")
+}
+
+/// One row per file; the summary is left to the reader of the CSV.
+pub fn write_csv(out: &mut impl Write, reports: &[FileReport]) -> io::Result<()> {
+    writeln!(
+        out,
+        "path,frames,duration,bitrate,vbr,frequency,version,mode,error"
+    )?;
+
+    for report in reports {
+        let path = csv_field(&report.path.to_string_lossy());
+        match &report.result {
+            Ok(info) => writeln!(
+                out,
+                "{path},{},{:.3},{:.1},{},{},{},{},",
+                info.frames,
+                info.duration,
+                info.bitrate,
+                info.vbr,
+                info.frequency,
+                info.version,
+                info.mode,
+            )?,
+            Err(err) => writeln!(out, "{path},,,,,,,,{}", csv_field(err))?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE: &str = "mp3-examples/test_data_100kb.mp3";
+
+    #[test]
+    fn test_file_info_and_summary() {
+        let info = FileInfo::scan(&mut Buffer::create_buffer_from_file(SAMPLE)).unwrap();
+
+        // The Info frame is left out.
+        assert_eq!(info.frames, 153);
+        assert!(info.vbr);
+        assert_eq!(info.bitrates.len(), 6);
+        assert_eq!(info.bitrates[&192], 63);
+        assert!((info.bitrate - 205.4).abs() < 0.1);
+        assert_eq!((info.version, info.frequency), (Version::MPEG1, 44100));
+
+        let reports = [
+            FileReport {
+                path: SAMPLE.into(),
+                result: Ok(info.clone()),
+            },
+            FileReport {
+                path: "missing.mp3".into(),
+                result: Err("not found".into()),
+            },
+            FileReport {
+                path: SAMPLE.into(),
+                result: Ok(info),
+            },
+        ];
+        let summary = Summary::create_from_reports(&reports);
+
+        assert_eq!((summary.files, summary.errors), (3, 1));
+        assert_eq!(summary.frames, 306);
+        assert_eq!(summary.vbr_files, 2);
+        assert_eq!(summary.bitrates[&192], 126);
+        assert_eq!(summary.versions, BTreeMap::from([("MPEG-1".into(), 2)]));
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &reports).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains("missing.mp3,,,,,,,,not found"));
+    }
+
+    #[test]
+    fn test_matches_path() {
+        let matches = |pattern: &str, path: &str| {
+            matches_path(
+                &components(Path::new(pattern)),
+                &components(Path::new(path)),
+            )
+        };
+
+        assert!(matches("music/*.mp3", "music/a.mp3"));
+        assert!(!matches("music/*.mp3", "music/album/a.mp3"));
+        assert!(matches("music/**/*.mp3", "music/a.mp3"));
+        assert!(matches("music/**/*.mp3", "music/album/disc 1/a.mp3"));
+        assert!(matches("music/track-?.mp3", "music/track-1.mp3"));
+        assert!(!matches("music/track-?.mp3", "music/track-10.mp3"));
+        assert!(matches("*live*.mp3", "b-live-2.mp3"));
+        assert!(!matches("*live*.mp3", "b-live-2.mp4"));
+
+        // Many `*` do not take exponential time.
+        let name = "a".repeat(100);
+        assert!(!matches(&format!("{}b", "a*".repeat(20)), &name));
+        assert!(matches(&"a*".repeat(20), &name));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files() {
+        let dir = std::env::temp_dir().join(format!("mp3-info-walk-{}", std::process::id()));
+        fs::create_dir_all(dir.join("album")).unwrap();
+        fs::copy(SAMPLE, dir.join("album/a.mp3")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("album/loop")).unwrap();
+
+        let (files, failed) = collect_files(&[dir.to_string_lossy().into_owned()]);
+        assert_eq!(files, [dir.join("album/a.mp3")]);
+        assert!(failed.is_empty());
+
+        let (mut files, mut failed) = (Vec::new(), Vec::new());
+        walk(&dir.join("missing"), &mut files, &mut failed);
+        assert!(files.is_empty());
+        assert_eq!(failed[0].path, dir.join("missing"));
+        assert!(failed[0].result.is_err());
+
+        let pattern = dir.join("misspelt/*.mp3");
+        let (files, failed) = collect_files(&[pattern.to_string_lossy().into_owned()]);
+        assert!(files.is_empty());
+        assert_eq!(failed[0].path, pattern);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,58 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
-use crate::{buffer::Buffer, error::ErrorType, header::Mode};
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    header::{Header, Mode, Version},
+};
+
+/// `slen1` and `slen2` of the MPEG-1 `scalefac_compress` values.
+pub const SLEN: [(u8, u8); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (3, 0),
+    (1, 1),
+    (1, 2),
+    (1, 3),
+    (2, 1),
+    (2, 2),
+    (2, 3),
+    (3, 1),
+    (3, 2),
+    (3, 3),
+    (4, 2),
+    (4, 3),
+];
+
+/// Bit offsets of fields from the start of the side info of a granule and channel.
+pub const PART_23_LENGTH_OFFSET: usize = 0;
+pub const BIG_VALUES_OFFSET: usize = 12;
+pub const GLOBAL_GAIN_OFFSET: usize = 21;
+
+/// Bits of the side info of a granule and channel, whose last bit is `count1_table_select`.
+pub fn channel_info_length(header: &Header) -> usize {
+    if header.version == Version::MPEG1 {
+        59
+    } else {
+        63
+    }
+}
+
+/// Bit offset of the side info of granule `gr` and channel `ch` from the start of the side info.
+pub fn channel_info_offset(header: &Header, gr: usize, ch: usize) -> usize {
+    let is_mono = header.mode == Mode::SingleChannel;
+    let channels = header.channel_count();
+    // main_data_begin, private_bits and, in MPEG-1, scfsi.
+    let common = match (header.version == Version::MPEG1, is_mono) {
+        (false, true) => 9,
+        (false, false) => 10,
+        (true, true) => 18,
+        (true, false) => 20,
+    };
+    common + (gr * channels + ch) * channel_info_length(header)
+}
 
 #[derive(Debug)]
 pub struct SideInfo {
@@ -10,9 +62,11 @@ pub struct SideInfo {
     pub granules: [Granule; 2],
 }
 
+/// Side info of one granule. Only the first `channel_count` entries of `channels` are used.
 #[derive(Debug, Default)]
 pub struct Granule {
-    pub channels: Vec<ChannelInfo>,
+    pub channels: [ChannelInfo; 2],
+    pub channel_count: usize,
 }
 
 #[derive(Debug, Default)]
@@ -20,7 +74,7 @@ pub struct ChannelInfo {
     pub part_23_length: u16,
     pub big_values: u16,
     pub global_gain: u8,
-    pub scalefac_compress: u8,
+    pub scalefac_compress: u16,
     pub windows_switching: bool,
     pub block_type: u8,
     pub mixed_block_flag: bool,
@@ -33,31 +87,32 @@ pub struct ChannelInfo {
 }
 
 impl Display for ChannelInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let part_23_length = format!("\t part_23_length: {:#2x}\n", self.part_23_length);
-        let big_values = format!("\t big_values: {:#2x}\n", self.big_values);
-        let global_gain = format!("\t global_gain: {:#8b}\n", self.global_gain);
-        let scalefac_compress = format!("\t scalefac_compress: {:#8b}\n", self.scalefac_compress);
-        let windows_switching = format!("\t windows_switching: {}\n", self.windows_switching);
-        let block_type = format!("\t block_type: {:#2b}\n", self.block_type);
-        let mixed_block_flag = format!("\t mixed_block_flag: {}\n", self.mixed_block_flag);
-        let table_select = format!(
-            "\t table_select: [{:#8b}, {:#8b}, {:#8b}]\n",
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "\t part_23_length: {:#2x}", self.part_23_length)?;
+        writeln!(f, "\t big_values: {:#2x}", self.big_values)?;
+        writeln!(f, "\t global_gain: {:#8b}", self.global_gain)?;
+        writeln!(f, "\t scalefac_compress: {:#8b}", self.scalefac_compress)?;
+        writeln!(f, "\t windows_switching: {}", self.windows_switching)?;
+        writeln!(f, "\t block_type: {:#2b}", self.block_type)?;
+        writeln!(f, "\t mixed_block_flag: {}", self.mixed_block_flag)?;
+        writeln!(
+            f,
+            "\t table_select: [{:#8b}, {:#8b}, {:#8b}]",
             self.table_select[0], self.table_select[1], self.table_select[2]
-        );
-        let subblock_gain = format!(
-            "\t subblock_gain: [{:#8b}, {:#8b}, {:#8b}]\n",
+        )?;
+        writeln!(
+            f,
+            "\t subblock_gain: [{:#8b}, {:#8b}, {:#8b}]",
             self.subblock_gain[0], self.subblock_gain[1], self.subblock_gain[2]
-        );
-        let region_count = format!(
-            "\t region_count: [{:#8b}, {:#8b}, {:#8b}]\n",
+        )?;
+        writeln!(
+            f,
+            "\t region_count: [{:#8b}, {:#8b}, {:#8b}]",
             self.region_count[0], self.region_count[1], self.region_count[2]
-        );
-        let preflag = format!("\t preflag: {}\n", self.preflag);
-        let scalefac_scale = format!("\t scalefac_scale: {}\n", self.scalefac_scale);
-        let count1_table_select = format!("\t count1_table_select: {}\n", self.count1_table_select);
-
-        write!(f, "{part_23_length}{big_values}{global_gain}{scalefac_compress}{windows_switching}{block_type}{mixed_block_flag}{table_select}{subblock_gain}{region_count}{preflag}{scalefac_scale}{count1_table_select}")
+        )?;
+        writeln!(f, "\t preflag: {}", self.preflag)?;
+        writeln!(f, "\t scalefac_scale: {}", self.scalefac_scale)?;
+        writeln!(f, "\t count1_table_select: {}", self.count1_table_select)
     }
 }
 
@@ -69,14 +124,15 @@ impl ChannelInfo {
 }
 
 impl Display for Granule {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut granule_result = Vec::new();
-
-        for (i, channel) in self.channels.iter().enumerate() {
-            granule_result.push(format!("\nChannel {i}: \n{channel}\n"));
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, channel) in self.channels().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "\nChannel {i}: \n{channel}\n")?;
         }
 
-        write!(f, "{}", granule_result.join("\n"))
+        Ok(())
     }
 }
 
@@ -85,83 +141,105 @@ impl Granule {
     fn new() -> Self {
         Self::default()
     }
+
+    pub fn channels(&self) -> &[ChannelInfo] {
+        &self.channels[..self.channel_count]
+    }
 }
 
 impl Display for SideInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let main_data_begin = format!("\t main_data_begin: {:#x}\n", self.main_data_begin);
-        let private_bits = format!("\t private_bits: {:#8b}\n", self.private_bits);
-        let scfsi = format!("\t scfsi: {:#8b}\n", self.scfsi);
-        let granule0 = format!("granule 0:\n{}\n", self.granules[0]);
-        let granule1 = format!("granule 1:\n{}\n", self.granules[1]);
-
-        write!(
-            f,
-            "{main_data_begin}{private_bits}{scfsi}\n{granule0}{granule1}\n"
-        )
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "\t main_data_begin: {:#x}", self.main_data_begin)?;
+        writeln!(f, "\t private_bits: {:#8b}", self.private_bits)?;
+        writeln!(f, "\t scfsi: {:#8b}", self.scfsi)?;
+        writeln!(f)?;
+        writeln!(f, "granule 0:\n{}", self.granules[0])?;
+        writeln!(f, "granule 1:\n{}", self.granules[1])?;
+        writeln!(f)
     }
 }
 
 impl SideInfo {
-    pub fn create_from_buffer(buffer: &mut Buffer, mode: &Mode) -> Result<Self, ErrorType> {
-        let is_mono = *mode == Mode::SingleChannel;
+    /// Bits of main data used by all granules and channels, scale factors included.
+    pub fn main_data_bits(&self) -> usize {
+        self.granules
+            .iter()
+            .flat_map(|granule| granule.channels())
+            .map(|channel| channel.part_23_length as usize)
+            .sum()
+    }
+
+    /// Parses the side info of an MPEG-1 frame, or of an MPEG-2/2.5 (LSF) frame which has a
+    /// single granule, no `scfsi` and no `preflag`. For LSF frames `granules[1]` stays empty.
+    pub fn create_from_buffer(buffer: &mut Buffer, header: &Header) -> Result<Self, ErrorType> {
+        let is_mono = header.mode == Mode::SingleChannel;
+        let is_lsf = header.version != Version::MPEG1;
 
-        let main_data_begin = buffer.get_bits(9).unwrap() as u16;
-        let private_bits: u8 = buffer.get_bits(if is_mono { 5 } else { 3 }).unwrap() as u8;
-        let scfsi = buffer.get_bits(if is_mono { 4 } else { 8 }).unwrap() as u8;
+        let (main_data_begin, private_bits, scfsi) = if is_lsf {
+            let main_data_begin = buffer.get_bits(8)? as u16;
+            let private_bits: u8 = buffer.get_bits(if is_mono { 1 } else { 2 })? as u8;
+            (main_data_begin, private_bits, 0)
+        } else {
+            let main_data_begin = buffer.get_bits(9)? as u16;
+            let private_bits: u8 = buffer.get_bits(if is_mono { 5 } else { 3 })? as u8;
+            let scfsi = buffer.get_bits(if is_mono { 4 } else { 8 })? as u8;
+            (main_data_begin, private_bits, scfsi)
+        };
 
-        let nch = if is_mono { 1 } else { 2 };
+        let nch = header.channel_count();
+        let ngr = header.granule_count();
         let mut granules: [Granule; 2] = [Granule::new(), Granule::new()];
         let mut part_23_sum: usize = 0;
 
-        for granule in granules.iter_mut() {
+        for granule in granules.iter_mut().take(ngr) {
             for _ in 0..nch {
                 let mut channel = ChannelInfo::new();
 
-                channel.part_23_length = buffer.get_bits(12).unwrap() as u16;
+                channel.part_23_length = buffer.get_bits(12)? as u16;
                 part_23_sum += channel.part_23_length as usize;
 
-                channel.big_values = buffer.get_bits(9).unwrap() as u16;
+                channel.big_values = buffer.get_bits(9)? as u16;
 
                 if channel.big_values > 288 {
                     return Err(ErrorType::BigValuesOutOfRange);
                 }
 
-                channel.global_gain = buffer.get_bits(8).unwrap() as u8;
-                channel.scalefac_compress = buffer.get_bits(4).unwrap() as u8;
-                channel.windows_switching = buffer.get_bits(1).unwrap() == 1;
+                channel.global_gain = buffer.get_bits(8)? as u8;
+                channel.scalefac_compress = buffer.get_bits(if is_lsf { 9 } else { 4 })? as u16;
+                channel.windows_switching = buffer.get_bits(1)? == 1;
 
                 if channel.windows_switching {
-                    channel.block_type = buffer.get_bits(2).unwrap() as u8;
+                    channel.block_type = buffer.get_bits(2)? as u8;
                     if channel.block_type == 0 {
                         return Err(ErrorType::BlockTypeForbidden);
                     }
 
-                    channel.mixed_block_flag = buffer.get_bits(1).unwrap() == 1;
+                    channel.mixed_block_flag = buffer.get_bits(1)? == 1;
 
                     for i in 0..2 {
-                        channel.table_select[i] = buffer.get_bits(5).unwrap() as u8;
+                        channel.table_select[i] = buffer.get_bits(5)? as u8;
                     }
 
                     for i in 0..3 {
-                        channel.subblock_gain[i] = buffer.get_bits(3).unwrap() as u8;
+                        channel.subblock_gain[i] = buffer.get_bits(3)? as u8;
                     }
                 } else {
                     for i in 0..3 {
-                        channel.table_select[i] = buffer.get_bits(5).unwrap() as u8;
+                        channel.table_select[i] = buffer.get_bits(5)? as u8;
                     }
 
-                    channel.region_count[0] = buffer.get_bits(4).unwrap() as u8;
-                    channel.region_count[1] = buffer.get_bits(3).unwrap() as u8;
+                    channel.region_count[0] = buffer.get_bits(4)? as u8;
+                    channel.region_count[1] = buffer.get_bits(3)? as u8;
                     channel.region_count[2] = 255;
                 }
 
-                let bits = buffer.get_bits(3).unwrap() as u8;
+                let bits = buffer.get_bits(if is_lsf { 2 } else { 3 })? as u8;
                 channel.preflag = bits & 4 == 4;
                 channel.scalefac_scale = bits & 2 == 2;
                 channel.count1_table_select = bits & 1 == 1;
 
-                granule.channels.push(channel);
+                granule.channels[granule.channel_count] = channel;
+                granule.channel_count += 1;
             }
         }
 
@@ -0,0 +1,277 @@
+use std::fmt::Display;
+
+use crate::{
+    decoder::{self, LINES},
+    gain::GAIN_STEP_DB,
+    side_info::Granule,
+    stream::AudioStream,
+};
+
+/// `global_gain` at which a quantised value of 1 is requantised to full scale.
+const UNITY_GAIN: f64 = 210.0;
+/// Level of the largest value big values can code, 8206^(4/3), relative to a value of 1, in dB.
+const MAX_VALUE_DB: f64 = 104.4;
+
+/// Limits of [`detect`].
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    /// Granules without big values coding at most this many bits, scale factors included, are
+    /// silent.
+    pub max_bits: u16,
+    /// Granules are also silent when the level of the largest value they can code, estimated
+    /// from `global_gain`, is below this many dBFS: ±1 without big values, or else the largest
+    /// value of all.
+    pub max_level_db: f64,
+    /// Shortest silent passage reported as silence, in seconds.
+    pub min_silence: f64,
+    /// Shortest silent passage reported as a dropout, in seconds, when it is shorter than
+    /// `min_silence` and has audio on both sides.
+    pub min_dropout: f64,
+    /// When set, passages are decoded and only kept if none of their granules has an RMS level
+    /// above this many dBFS.
+    pub confirm_rms_db: Option<f64>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            max_bits: 64,
+            max_level_db: -60.0,
+            min_silence: 2.0,
+            min_dropout: 0.01,
+            confirm_rms_db: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GapKind {
+    Silence,
+    Dropout,
+}
+
+/// A silent passage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gap {
+    pub kind: GapKind,
+    /// Start and end in seconds from the start of the decoded audio, after the encoder delay of
+    /// the LAME or VBRI header and the decoder delay.
+    pub start: f64,
+    pub end: f64,
+    /// First and last audio frame of the passage.
+    pub frames: (usize, usize),
+    /// RMS level of the loudest decoded granule, in dBFS, when confirmed by decoding.
+    pub rms_db: Option<f64>,
+}
+
+impl Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            GapKind::Silence => "silence",
+            GapKind::Dropout => "dropout",
+        };
+        write!(
+            f,
+            "{kind} {:.3}s - {:.3}s ({:.3}s), frames {}-{}",
+            self.start,
+            self.end,
+            self.end - self.start,
+            self.frames.0,
+            self.frames.1
+        )?;
+        match self.rms_db {
+            // Nothing but zeros decoded.
+            Some(rms_db) if rms_db == f64::NEG_INFINITY => write!(f, ", digital silence")?,
+            Some(rms_db) => write!(f, ", decoded RMS {rms_db:.1} dBFS")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Whether every channel of `granule` is silent, judging by its side info only.
+fn is_silent(granule: &Granule, thresholds: &Thresholds) -> bool {
+    granule.channels().iter().all(|channel| {
+        let mut level_db = (channel.global_gain as f64 - UNITY_GAIN) * GAIN_STEP_DB;
+        if channel.big_values > 0 {
+            level_db += MAX_VALUE_DB;
+        }
+        channel.part_23_length == 0
+            || level_db < thresholds.max_level_db
+            || channel.big_values == 0 && channel.part_23_length <= thresholds.max_bits
+    })
+}
+
+/// Mean square of every decoded granule of `stream`, of its loudest channel.
+fn granule_power(stream: &AudioStream) -> Vec<f32> {
+    decoder::samples(stream)
+        .flat_map(|(_, samples)| {
+            let granules = samples[0].len() / LINES;
+            (0..granules).map(move |gr| {
+                samples
+                    .iter()
+                    .map(|channel| {
+                        let granule = &channel[gr * LINES..(gr + 1) * LINES];
+                        granule.iter().map(|sample| sample * sample).sum::<f32>() / LINES as f32
+                    })
+                    .fold(0.0, f32::max)
+            })
+        })
+        .collect()
+}
+
+/// Finds silent passages and dropouts in `stream` from the side info of its granules: empty
+/// granules, granules whose `global_gain` is too low for anything they code to be heard, and
+/// granules with nothing but a few count1 values. Passages at least `min_silence` long are
+/// silences; shorter ones with audio on both sides are dropouts. With `confirm_rms_db`, the
+/// stream is decoded and passages that are louder are dropped.
+pub fn detect(stream: &AudioStream, thresholds: &Thresholds) -> Vec<Gap> {
+    let header = stream.header();
+    let frequency = header.get_frequency().unwrap_or(44100) as f64;
+    let delay = stream.delay() as f64;
+    let granule_count = header.granule_count();
+    let time = |granule: usize| ((granule * LINES) as f64 - delay).max(0.0) / frequency;
+
+    let silent: Vec<bool> = stream
+        .frames()
+        .iter()
+        .flat_map(|frame| &frame.side_info().granules[..granule_count])
+        .map(|granule| is_silent(granule, thresholds))
+        .collect();
+
+    // Runs of silent granules, as [start, end).
+    let mut runs = Vec::new();
+    let mut run_start = None;
+    for (i, &silent) in silent.iter().chain([&false]).enumerate() {
+        match (silent, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                runs.push((start, i));
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let power = thresholds
+        .confirm_rms_db
+        .map(|_| granule_power(stream))
+        .unwrap_or_default();
+
+    runs.into_iter()
+        .filter_map(|(start, end)| {
+            let seconds = ((end - start) * LINES) as f64 / frequency;
+            let kind = if seconds >= thresholds.min_silence {
+                GapKind::Silence
+            } else if seconds >= thresholds.min_dropout && start > 0 && end < silent.len() {
+                GapKind::Dropout
+            } else {
+                return None;
+            };
+
+            // The decoder delays the audio by about a granule, and the first and last granule of
+            // the passage still overlap the audio around it.
+            let decoded = if end - start > 2 {
+                start + 2..end
+            } else {
+                start + 1..end + 1
+            };
+            let rms_db = thresholds.confirm_rms_db.map(|_| {
+                let loudest = power[decoded.start.min(power.len())..decoded.end.min(power.len())]
+                    .iter()
+                    .fold(0.0, |loudest: f32, &power| loudest.max(power));
+                10.0 * (loudest as f64).log10()
+            });
+            if let (Some(rms_db), Some(limit)) = (rms_db, thresholds.confirm_rms_db) {
+                if rms_db > limit {
+                    return None;
+                }
+            }
+
+            Some(Gap {
+                kind,
+                start: time(start),
+                end: time(end),
+                frames: (start / granule_count, (end - 1) / granule_count),
+                rms_db,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        buffer::Buffer,
+        mute::{self, MuteRange},
+    };
+
+    #[test]
+    fn test_detect() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let stream = AudioStream::create_from_buffer(buffer.share()).unwrap();
+        let thresholds = Thresholds {
+            min_silence: 0.5,
+            confirm_rms_db: Some(-60.0),
+            ..Default::default()
+        };
+        // The empty granules of the encoder delay are neither.
+        assert!(detect(&stream, &thresholds).is_empty());
+
+        // Every granule is silent with a limit above full scale, until decoded.
+        let loud = Thresholds {
+            max_level_db: 1000.0,
+            confirm_rms_db: None,
+            ..thresholds
+        };
+        assert_eq!(detect(&stream, &loud).len(), 1);
+        assert!(detect(
+            &stream,
+            &Thresholds {
+                confirm_rms_db: Some(-60.0),
+                ..loud
+            }
+        )
+        .is_empty());
+
+        // A second muted, and 50ms muted and cleared.
+        let edit = mute::mute(
+            &mut buffer,
+            &[MuteRange {
+                start: 1.0,
+                end: 2.0,
+            }],
+            0.0,
+            false,
+        )
+        .unwrap();
+        let ranges = [MuteRange {
+            start: 3.0,
+            end: 3.05,
+        }];
+        let edit = mute::mute(
+            &mut Buffer::create_buffer_from_data(edit.data),
+            &ranges,
+            0.0,
+            true,
+        )
+        .unwrap();
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(edit.data)).unwrap();
+
+        let gaps = detect(&stream, &thresholds);
+        assert_eq!(gaps.len(), 2);
+        assert_eq!(gaps[0].kind, GapKind::Silence);
+        assert!((gaps[0].start - 1.0).abs() < 0.02 && (gaps[0].end - 2.0).abs() < 0.02);
+        assert_eq!(gaps[1].kind, GapKind::Dropout);
+        assert!((gaps[1].start - 3.0).abs() < 0.02 && (gaps[1].end - 3.05).abs() < 0.02);
+        assert!(gaps.iter().all(|gap| gap.rms_db.unwrap() < -100.0));
+        assert_eq!(gaps[1].rms_db, Some(f64::NEG_INFINITY));
+        assert!(
+            gaps[1].to_string().ends_with(", digital silence"),
+            "{}",
+            gaps[1]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::{self, Write};
+
+use crate::{
+    decoder::{Decoder, LINES},
+    stream::AudioStream,
+};
+
+/// Levels shown, in dB below the loudest line of the file; anything quieter is black.
+const RANGE_DB: f32 = 100.0;
+/// Colours from the quietest to the loudest level, interpolated in between.
+const PALETTE: [[u8; 3]; 6] = [
+    [0, 0, 0],
+    [40, 10, 90],
+    [140, 20, 120],
+    [230, 70, 50],
+    [250, 180, 40],
+    [255, 255, 220],
+];
+/// Most columns drawn without a width given; longer files are squeezed into it.
+pub const DEFAULT_WIDTH: usize = 1600;
+/// Rows between the panels of the channels.
+const GAP: usize = 4;
+const GAP_COLOR: [u8; 3] = [128, 128, 128];
+
+/// A spectrogram image. Time runs from left to right and frequency from 0 at the bottom to half
+/// the sample rate at the top of every panel, one row per spectral line.
+pub struct Spectrogram {
+    pub width: usize,
+    pub height: usize,
+    /// RGB, row after row from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Spectrogram {
+    pub fn write_png(&self, out: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(writer.finish()?)
+    }
+}
+
+fn color(level: f32) -> [u8; 3] {
+    let position = level.clamp(0.0, 1.0) * (PALETTE.len() - 1) as f32;
+    let index = (position as usize).min(PALETTE.len() - 2);
+    let fraction = position - index as f32;
+
+    core::array::from_fn(|i| {
+        let (from, to) = (PALETTE[index][i] as f32, PALETTE[index + 1][i] as f32);
+        (from + (to - from) * fraction).round() as u8
+    })
+}
+
+/// Renders the spectral lines of `stream`, three columns to a granule: a granule of long blocks
+/// spans all three, while each window of a short block gets its own column at its 192-line
+/// resolution, three rows to a line. With `per_channel` every channel gets its own panel, from
+/// top to bottom, otherwise their power is averaged. `width`, [`DEFAULT_WIDTH`] at most by
+/// default, squeezes the columns into that many as they are decoded, keeping the loudest.
+/// Granules that cannot be decoded, for want of the bit reservoir bytes before the first frame,
+/// are left silent.
+pub fn spectrogram(stream: &AudioStream, per_channel: bool, width: Option<usize>) -> Spectrogram {
+    let frames = stream.frames();
+    let granules: usize = frames
+        .iter()
+        .map(|frame| frame.header().granule_count())
+        .sum();
+    let columns = 3 * granules;
+    let width = width.unwrap_or(DEFAULT_WIDTH).clamp(1, columns.max(1));
+
+    let panels = if per_channel {
+        stream.channel_count()
+    } else {
+        1
+    };
+    // Power of every line, by panel, then column, then line.
+    let mut power = vec![0.0f32; panels * width * LINES];
+
+    let mut decoder = Decoder::new();
+    let mut window_power = vec![0.0f32; panels * LINES];
+    let mut column = 0;
+    for frame in frames {
+        let Ok(spectra) = decoder.decode_spectra(frame, stream.frame_data(frame)) else {
+            column += 3 * frame.header().granule_count();
+            continue;
+        };
+
+        for granule in spectra {
+            for window in 0..3 {
+                window_power.fill(0.0);
+                for (ch, spectrum) in granule.iter().enumerate() {
+                    let (panel, weight) = if per_channel {
+                        (ch, 1.0)
+                    } else {
+                        (0, 1.0 / granule.len() as f32)
+                    };
+                    let short = spectrum
+                        .has_short_blocks()
+                        .then(|| spectrum.short_window(window));
+
+                    for (line, power) in window_power[panel * LINES..][..LINES]
+                        .iter_mut()
+                        .enumerate()
+                    {
+                        let value = match &short {
+                            Some(short) => short[line / 3],
+                            None => spectrum.lines[line],
+                        };
+                        *power += value * value * weight;
+                    }
+                }
+
+                // Columns covering several windows keep the loudest of each line.
+                let x = column * width / columns;
+                for panel in 0..panels {
+                    let start = (panel * width + x) * LINES;
+                    for (power, window) in power[start..start + LINES]
+                        .iter_mut()
+                        .zip(&window_power[panel * LINES..][..LINES])
+                    {
+                        *power = power.max(*window);
+                    }
+                }
+                column += 1;
+            }
+        }
+    }
+
+    let loudest = power
+        .iter()
+        .copied()
+        .fold(0.0, f32::max)
+        .max(f32::MIN_POSITIVE);
+    let height = panels * LINES + (panels - 1) * GAP;
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for panel in 0..panels {
+        if panel > 0 {
+            for _ in 0..GAP * width {
+                pixels.extend_from_slice(&GAP_COLOR);
+            }
+        }
+        for line in (0..LINES).rev() {
+            for x in 0..width {
+                let db = 10.0 * (power[(panel * width + x) * LINES + line] / loudest).log10();
+                pixels.extend_from_slice(&color(1.0 + db / RANGE_DB));
+            }
+        }
+    }
+
+    Spectrogram {
+        width,
+        height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer::Buffer, header::Mode};
+
+    #[test]
+    fn test_spectrogram() {
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+
+        let image = spectrogram(&stream, false, None);
+        assert_eq!((image.width, image.height), (3 * 2 * 153, LINES));
+        assert_eq!(image.pixels.len(), image.width * image.height * 3);
+        let row = |image: &Spectrogram, y: usize| {
+            image.pixels[y * image.width * 3..(y + 1) * image.width * 3].to_vec()
+        };
+        // Nothing above the lowpass, something at the bottom.
+        assert!(row(&image, 0).iter().all(|&value| value == 0));
+        assert!(row(&image, LINES - 1).iter().any(|&value| value > 0));
+
+        let image = spectrogram(&stream, true, Some(400));
+        assert_eq!((image.width, image.height), (400, 2 * LINES + GAP));
+        assert!(row(&image, LINES).chunks(3).all(|pixel| pixel == GAP_COLOR));
+
+        // A stream starting with a mono frame still gets a panel per channel of the others.
+        let mut data = std::fs::read("mp3-examples/test_data_100kb.mp3").unwrap();
+        data[3] |= 0xc0;
+        let stream =
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(data)).unwrap();
+        assert_eq!(stream.header().mode, Mode::SingleChannel);
+        let mono_first = spectrogram(&stream, true, Some(400));
+        assert_eq!(mono_first.height, image.height);
+
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        assert_eq!(color(0.0), PALETTE[0]);
+        assert_eq!(color(1.0), PALETTE[5]);
+    }
+}
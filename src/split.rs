@@ -0,0 +1,238 @@
+use crate::{
+    error::ErrorType,
+    frame::{self, Frame},
+    stream::AudioStream,
+    xing::{XingHeader, MAX_LAME_DELAY},
+};
+
+/// A granule is considered quiet when it has no big values and at most this many bits of
+/// scale factors and count1 data.
+const QUIET_PART_23_LENGTH: u16 = 64;
+
+#[derive(Debug)]
+pub enum SplitMode {
+    /// Chunks of this many seconds.
+    Duration(f64),
+    /// Chunks whose audio frames take at most this many bytes. The Info frame, the priming
+    /// frames and a copied tag come on top.
+    Size(usize),
+    /// Cut in the middle of every quiet passage lasting at least this many seconds.
+    Silence(f64),
+}
+
+impl SplitMode {
+    /// Index of the first frame of every chunk. The first chunk always starts at 0. Cuts by size
+    /// go back to the last frame needing at most `max_priming` priming frames, so that chunks
+    /// stay under the limit.
+    pub fn cut_points(&self, frames: &[Frame], max_priming: usize) -> Vec<usize> {
+        let mut cuts = vec![0];
+        let Some(first) = frames.first() else {
+            return cuts;
+        };
+        let samples_per_frame = first.header().samples_per_frame() as f64;
+        let frequency = first.header().get_frequency().unwrap_or(44100) as f64;
+
+        match self {
+            SplitMode::Duration(seconds) => {
+                let frames_per_chunk = (seconds * frequency / samples_per_frame).max(1.0);
+                for k in 1.. {
+                    let cut = (k as f64 * frames_per_chunk).round() as usize;
+                    if cut >= frames.len() {
+                        break;
+                    }
+                    cuts.push(cut);
+                }
+            }
+            SplitMode::Size(bytes) => {
+                let mut chunk_start = 0;
+                let mut chunk_bytes = 0;
+                for (i, frame) in frames.iter().enumerate() {
+                    if chunk_bytes > 0 && chunk_bytes + frame.length_byte() > *bytes {
+                        let cut = (chunk_start + 1..=i)
+                            .rev()
+                            .find(|&cut| frame::priming_frames(frames, cut) <= max_priming)
+                            .unwrap_or(i);
+                        cuts.push(cut);
+                        chunk_start = cut;
+                        chunk_bytes = frames[cut..i].iter().map(Frame::length_byte).sum();
+                    }
+                    chunk_bytes += frame.length_byte();
+                }
+            }
+            SplitMode::Silence(seconds) => {
+                let min_frames = (seconds * frequency / samples_per_frame).ceil().max(1.0) as usize;
+                let mut run_start = None;
+                for (i, frame) in frames.iter().enumerate() {
+                    match (is_quiet(frame), run_start) {
+                        (true, None) => run_start = Some(i),
+                        (false, Some(start)) => {
+                            if i - start >= min_frames && start > 0 {
+                                cuts.push((start + i) / 2);
+                            }
+                            run_start = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        cuts
+    }
+}
+
+fn is_quiet(frame: &Frame) -> bool {
+    frame
+        .side_info()
+        .granules
+        .iter()
+        .flat_map(|granule| granule.channels())
+        .all(|channel| channel.big_values == 0 && channel.part_23_length <= QUIET_PART_23_LENGTH)
+}
+
+/// Splits `stream` at frame boundaries into standalone files, each with its own Xing/Info frame
+/// and, with `copy_tag`, a copy of the ID3v2 tag of the input.
+///
+/// Every chunk after the first starts with the frames needed to refill the bit reservoir and the
+/// IMDCT overlap of its first frame, and every chunk before the last ends with one extra frame to
+/// complete the overlap of its last one. These frames are declared as encoder delay and padding in
+/// the LAME header, so gapless players output exactly the samples of the chunk. Streams without a
+/// LAME header get a plain Xing/Info frame and play the overlapping frames.
+///
+/// The encoder delay holds at most 4095 samples, which low bitrates can take more priming frames
+/// than. A cut that would need more is moved back to the last frame that needs few enough when
+/// cutting by size, and otherwise to the next one, and `ReservoirTooDeep` is returned when there
+/// is none before the next cut.
+pub fn split(
+    stream: &AudioStream,
+    mode: &SplitMode,
+    copy_tag: bool,
+) -> Result<Vec<Vec<u8>>, ErrorType> {
+    let frames = stream.frames();
+    let samples_per_frame = stream.header().samples_per_frame();
+    let max_priming = if stream.lame().is_some() {
+        MAX_LAME_DELAY / samples_per_frame
+    } else {
+        usize::MAX
+    };
+
+    let mut cuts = mode.cut_points(frames, max_priming);
+    cuts.push(frames.len());
+    for i in 1..cuts.len() - 1 {
+        cuts[i] = (cuts[i]..cuts[i + 1])
+            .find(|&cut| frame::priming_frames(frames, cut) <= max_priming)
+            .ok_or(ErrorType::ReservoirTooDeep)?;
+    }
+
+    let mut chunks = Vec::with_capacity(cuts.len() - 1);
+    for (i, range) in cuts.windows(2).enumerate() {
+        let (start, end) = (range[0], range[1]);
+        let is_first = i == 0;
+        let is_last = end == frames.len();
+
+        let priming = frame::priming_frames(frames, start);
+        let trailing = if is_last { 0 } else { 1 };
+
+        let audio: Vec<&[u8]> = frames[start - priming..end + trailing]
+            .iter()
+            .map(|frame| stream.frame_data(frame))
+            .collect();
+
+        let mut base = stream.xing().cloned();
+        if let Some(lame) = base.as_mut().and_then(|base| base.lame.as_mut()) {
+            if !is_first {
+                lame.encoder_delay = (priming * samples_per_frame) as u16;
+            }
+            if !is_last {
+                lame.encoder_padding = (trailing * samples_per_frame) as u16;
+            }
+        }
+
+        let mut chunk = if copy_tag {
+            stream.id3v2().to_vec()
+        } else {
+            Vec::new()
+        };
+        chunk.extend(XingHeader::build_stream(
+            &audio,
+            frames[start - priming].header(),
+            base.as_ref(),
+        )?);
+
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_split_by_duration() {
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+
+        // 153 frames of 1152 samples at 44.1kHz is just under 4 seconds.
+        let chunks = split(&stream, &SplitMode::Duration(1.0), false).unwrap();
+        assert_eq!(chunks.len(), 4);
+
+        for chunk in chunks {
+            let mut buffer = Buffer::create_buffer_from_data(chunk);
+            let frames = buffer.extract_frames();
+            let xing =
+                XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+
+            assert_eq!(xing.frames, Some(frames.len() as u32 - 1));
+            assert_eq!(xing.bytes, Some(buffer.data.len() as u32));
+        }
+    }
+
+    #[test]
+    fn test_split_low_bitrate() {
+        // The Info frame of the sample, with its LAME header, then 32kb/sec frames of 68 bytes of
+        // main data, all but two pointing 511 bytes back: 8 frames of reservoir.
+        let info = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3").data[..417]
+            .to_vec();
+        let low_bitrate = |count: usize| {
+            let mut data = info.clone();
+            for i in 0..count {
+                let mut frame = vec![0; 104];
+                frame[..4].copy_from_slice(&[0xff, 0xfb, 0x10, 0x00]);
+                if i != 24 && i != 25 {
+                    frame[4..6].copy_from_slice(&[0xff, 0x80]);
+                }
+                data.extend(frame);
+            }
+            AudioStream::create_from_buffer(Buffer::create_buffer_from_data(data)).unwrap()
+        };
+
+        // The cut at frame 19 moves to 25, which needs a single priming frame.
+        let stream = low_bitrate(35);
+        assert_eq!(frame::priming_frames(stream.frames(), 19), 1 + 8);
+        let chunks = split(&stream, &SplitMode::Duration(0.5), false).unwrap();
+        assert_eq!(chunks.len(), 2);
+        let mut buffer = Buffer::create_buffer_from_data(chunks[1].clone());
+        assert_eq!(buffer.extract_frames().len(), 1 + 1 + 10);
+
+        // By size, the cut at frame 26 goes back to 25 instead, keeping the first chunk under
+        // the limit.
+        let chunks = split(&stream, &SplitMode::Size(26 * 104), false).unwrap();
+        assert_eq!(chunks.len(), 2);
+        let mut buffer = Buffer::create_buffer_from_data(chunks[0].clone());
+        assert_eq!(buffer.extract_frames().len(), 1 + 25 + 1);
+        let mut buffer = Buffer::create_buffer_from_data(chunks[1].clone());
+        assert_eq!(buffer.extract_frames().len(), 1 + 1 + 10);
+
+        // Nothing after the cut at frame 38 can be primed.
+        let stream = low_bitrate(40);
+        assert_eq!(
+            split(&stream, &SplitMode::Duration(0.5), false).err(),
+            Some(ErrorType::ReservoirTooDeep)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    buffer::Buffer,
+    error::ErrorType,
+    frame::Frame,
+    header::Header,
+    mp3_file, tag,
+    xing::{LameHeader, VbriHeader, XingHeader},
+};
+
+/// Audio frames of a file, without its ID3 tags and Xing/Info or VBRI frame.
+pub struct AudioStream<'a> {
+    buffer: Buffer<'a>,
+    frames: Vec<Frame>,
+    xing: Option<XingHeader>,
+    vbri: Option<VbriHeader>,
+    /// The Xing/Info or VBRI frame `xing` or `vbri` was read from.
+    xing_frame: Option<Frame>,
+}
+
+impl<'a> AudioStream<'a> {
+    pub fn create_from_buffer(mut buffer: Buffer<'a>) -> Result<Self, ErrorType> {
+        let frames = buffer.extract_frames();
+        Self::create_from_frames(buffer, frames)
+    }
+
+    /// Same as [`AudioStream::create_from_buffer`], with the `frames` of `buffer` already
+    /// extracted.
+    pub fn create_from_frames(
+        buffer: Buffer<'a>,
+        mut frames: Vec<Frame>,
+    ) -> Result<Self, ErrorType> {
+        let first = frames.first().ok_or(ErrorType::NoFrames)?;
+
+        let xing = XingHeader::parse(buffer.frame_data(first), first.header());
+        let vbri = xing
+            .is_none()
+            .then(|| VbriHeader::parse(buffer.frame_data(first)))
+            .flatten();
+        let xing_frame = (xing.is_some() || vbri.is_some()).then(|| frames.remove(0));
+
+        if frames.is_empty() {
+            return Err(ErrorType::NoFrames);
+        }
+
+        Ok(Self {
+            buffer,
+            frames,
+            xing,
+            vbri,
+            xing_frame,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        self.frames[0].header()
+    }
+
+    /// Most channels coded in a frame, as the mode may change from frame to frame.
+    pub fn channel_count(&self) -> usize {
+        self.frames
+            .iter()
+            .map(|frame| frame.header().channel_count())
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn frame_data(&self, frame: &Frame) -> &[u8] {
+        self.buffer.frame_data(frame)
+    }
+
+    pub fn id3v2(&self) -> &[u8] {
+        &self.buffer.data[..tag::id3v2_length(&self.buffer.data)]
+    }
+
+    pub fn xing(&self) -> Option<&XingHeader> {
+        self.xing.as_ref()
+    }
+
+    pub fn vbri(&self) -> Option<&VbriHeader> {
+        self.vbri.as_ref()
+    }
+
+    pub fn lame(&self) -> Option<&LameHeader> {
+        self.xing.as_ref().and_then(|xing| xing.lame.as_ref())
+    }
+
+    /// Samples a gapless player drops from the decoder output before sample 0; see
+    /// [`mp3_file::gapless_delay`].
+    pub fn delay(&self) -> u64 {
+        mp3_file::gapless_delay(self.xing(), self.vbri())
+    }
+
+    /// Rewrites the music CRC of the LAME header in `data`, a copy of the bytes of the stream
+    /// whose audio frames were edited in place, to match them.
+    pub fn update_music_crc(&self, data: &mut [u8]) {
+        let (Some(xing), Some(info)) = (&self.xing, &self.xing_frame) else {
+            return;
+        };
+        let first = self.frames[0].offset_byte();
+        let last = &self.frames[self.frames.len() - 1];
+
+        let (info_data, body) = data.split_at_mut(first);
+        xing.update_music_crc(
+            &mut info_data[info.offset_byte()..][..info.length_byte()],
+            info.header(),
+            &body[..last.offset_byte() + last.length_byte() - first],
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::f32::consts::PI;
+
+use crate::{
+    decoder::{Spectrum, LINES},
+    side_info::ChannelInfo,
+};
+
+/// Subbands of the polyphase filterbank, each 18 lines of a granule wide.
+const SUBBANDS: usize = 32;
+/// Lines of a subband.
+const SUBBAND_LINES: usize = LINES / SUBBANDS;
+
+/// Coefficients c_i of the aliasing reduction butterflies.
+const ALIAS: [f32; 8] = [
+    -0.6, -0.535, -0.33, -0.185, -0.095, -0.041, -0.0142, -0.0037,
+];
+
+/// The window D of the synthesis filterbank, in units of 2^-16.
+#[rustfmt::skip]
+const WINDOW: [i32; 512] = [
+    0, -1, -1, -1, -1, -1, -1, -2, -2, -2, -2, -3, -3, -4, -4, -5,
+    -5, -6, -7, -7, -8, -9, -10, -11, -13, -14, -16, -17, -19, -21, -24, -26,
+    -29, -31, -35, -38, -41, -45, -49, -53, -58, -63, -68, -73, -79, -85, -91, -97,
+    -104, -111, -117, -125, -132, -139, -147, -154, -161, -169, -176, -183, -190, -196, -202, -208,
+    213, 218, 222, 225, 227, 228, 228, 227, 224, 221, 215, 208, 200, 189, 177, 163,
+    146, 127, 106, 83, 57, 29, -2, -36, -72, -111, -153, -197, -244, -294, -347, -401,
+    -459, -519, -581, -645, -711, -779, -848, -919, -991, -1064, -1137, -1210, -1283, -1356, -1428, -1498,
+    -1567, -1634, -1698, -1759, -1817, -1870, -1919, -1962, -2001, -2032, -2057, -2075, -2085, -2087, -2080, -2063,
+    2037, 2000, 1952, 1893, 1822, 1739, 1644, 1535, 1414, 1280, 1131, 970, 794, 605, 402, 185,
+    -45, -288, -545, -814, -1095, -1388, -1692, -2006, -2330, -2663, -3004, -3351, -3705, -4063, -4425, -4788,
+    -5153, -5517, -5879, -6237, -6589, -6935, -7271, -7597, -7910, -8209, -8491, -8755, -8998, -9219, -9416, -9585,
+    -9727, -9838, -9916, -9959, -9966, -9935, -9863, -9750, -9592, -9389, -9139, -8840, -8492, -8092, -7640, -7134,
+    6574, 5959, 5288, 4561, 3776, 2935, 2037, 1082, 70, -998, -2122, -3300, -4533, -5818, -7154, -8540,
+    -9975, -11455, -12980, -14548, -16155, -17799, -19478, -21189, -22929, -24694, -26482, -28289, -30112, -31947, -33791, -35640,
+    -37489, -39336, -41176, -43006, -44821, -46617, -48390, -50137, -51853, -53534, -55178, -56778, -58333, -59838, -61289, -62684,
+    -64019, -65290, -66494, -67629, -68692, -69679, -70590, -71420, -72169, -72835, -73415, -73908, -74313, -74630, -74856, -74992,
+    75038, 74992, 74856, 74630, 74313, 73908, 73415, 72835, 72169, 71420, 70590, 69679, 68692, 67629, 66494, 65290,
+    64019, 62684, 61289, 59838, 58333, 56778, 55178, 53534, 51853, 50137, 48390, 46617, 44821, 43006, 41176, 39336,
+    37489, 35640, 33791, 31947, 30112, 28289, 26482, 24694, 22929, 21189, 19478, 17799, 16155, 14548, 12980, 11455,
+    9975, 8540, 7154, 5818, 4533, 3300, 2122, 998, -70, -1082, -2037, -2935, -3776, -4561, -5288, -5959,
+    6574, 7134, 7640, 8092, 8492, 8840, 9139, 9389, 9592, 9750, 9863, 9935, 9966, 9959, 9916, 9838,
+    9727, 9585, 9416, 9219, 8998, 8755, 8491, 8209, 7910, 7597, 7271, 6935, 6589, 6237, 5879, 5517,
+    5153, 4788, 4425, 4063, 3705, 3351, 3004, 2663, 2330, 2006, 1692, 1388, 1095, 814, 545, 288,
+    45, -185, -402, -605, -794, -970, -1131, -1280, -1414, -1535, -1644, -1739, -1822, -1893, -1952, -2000,
+    2037, 2063, 2080, 2087, 2085, 2075, 2057, 2032, 2001, 1962, 1919, 1870, 1817, 1759, 1698, 1634,
+    1567, 1498, 1428, 1356, 1283, 1210, 1137, 1064, 991, 919, 848, 779, 711, 645, 581, 519,
+    459, 401, 347, 294, 244, 197, 153, 111, 72, 36, 2, -29, -57, -83, -106, -127,
+    -146, -163, -177, -189, -200, -208, -215, -221, -224, -227, -228, -228, -227, -225, -222, -218,
+    213, 208, 202, 196, 190, 183, 176, 169, 161, 154, 147, 139, 132, 125, 117, 111,
+    104, 97, 91, 85, 79, 73, 68, 63, 58, 53, 49, 45, 41, 38, 35, 31,
+    29, 26, 24, 21, 19, 17, 16, 14, 13, 11, 10, 9, 8, 7, 7, 6,
+    5, 5, 4, 4, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1,
+];
+
+/// Turns the spectral lines of one channel into samples: aliasing reduction, the inverse MDCT
+/// with overlap-add and the polyphase synthesis filterbank. Both filters keep state from granule
+/// to granule, so a channel needs its own `Synthesis` and must get its granules in order.
+pub struct Synthesis {
+    /// Second half of the inverse MDCT of the previous granule, by subband.
+    overlap: [[f32; SUBBAND_LINES]; SUBBANDS],
+    /// The last 16 vectors of the matrixing, newest first.
+    v: [f32; 1024],
+    /// `cos(π/72 (2i + 19)(2k + 1))` of the 36-point inverse MDCT, by i then k.
+    imdct_long: [[f32; 18]; 36],
+    /// `cos(π/24 (2i + 7)(2k + 1))` of the 12-point inverse MDCT of short windows.
+    imdct_short: [[f32; 6]; 12],
+    /// `cos((16 + i)(2k + 1)π/64)` of the matrixing, by i then k.
+    matrix: [[f32; SUBBANDS]; 64],
+}
+
+impl Default for Synthesis {
+    fn default() -> Self {
+        Self {
+            overlap: [[0.0; SUBBAND_LINES]; SUBBANDS],
+            v: [0.0; 1024],
+            imdct_long: core::array::from_fn(|i| {
+                core::array::from_fn(|k| {
+                    (PI / 72.0 * (2 * i + 19) as f32 * (2 * k + 1) as f32).cos()
+                })
+            }),
+            imdct_short: core::array::from_fn(|i| {
+                core::array::from_fn(|k| {
+                    (PI / 24.0 * (2 * i + 7) as f32 * (2 * k + 1) as f32).cos()
+                })
+            }),
+            matrix: core::array::from_fn(|i| {
+                core::array::from_fn(|k| (PI / 64.0 * (16 + i) as f32 * (2 * k + 1) as f32).cos())
+            }),
+        }
+    }
+}
+
+/// Window of the 36-point inverse MDCT for `block_type` 0 (normal), 1 (start) or 3 (stop).
+fn long_window(block_type: u8, i: usize) -> f32 {
+    let sine = |i: usize, n: usize| (PI / n as f32 * (i as f32 + 0.5)).sin();
+    match (block_type, i) {
+        (1, 18..=23) | (3, 12..=17) => 1.0,
+        (1, 24..=29) => sine(i - 18, 12),
+        (1, 30..) | (3, ..=5) => 0.0,
+        (3, 6..=11) => sine(i - 6, 12),
+        _ => sine(i, 36),
+    }
+}
+
+impl Synthesis {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The 576 samples of the granule whose lines are `spectrum`, coded with the block type of
+    /// `channel`, at full scale ±1.
+    pub fn process(&mut self, spectrum: &Spectrum, channel: &ChannelInfo) -> [f32; LINES] {
+        let mut lines = spectrum.lines;
+        // Subbands of long blocks: all of them without short blocks, those before
+        // `short_start` in mixed blocks.
+        let long = spectrum.short_start.min(LINES) / SUBBAND_LINES;
+        antialias(&mut lines, long);
+
+        let mut subbands = [[0.0; SUBBAND_LINES]; SUBBANDS];
+        for (sb, samples) in subbands.iter_mut().enumerate() {
+            let lines = &lines[sb * SUBBAND_LINES..][..SUBBAND_LINES];
+            let output = if sb < long {
+                // The long subbands of mixed blocks use the normal window.
+                let block_type = if spectrum.has_short_blocks() {
+                    0
+                } else {
+                    channel.block_type
+                };
+                self.imdct_long(lines, block_type)
+            } else {
+                self.imdct_short(lines)
+            };
+
+            for (i, sample) in samples.iter_mut().enumerate() {
+                *sample = output[i] + self.overlap[sb][i];
+                // Frequency inversion of the odd subbands.
+                if sb % 2 == 1 && i % 2 == 1 {
+                    *sample = -*sample;
+                }
+            }
+            self.overlap[sb].copy_from_slice(&output[SUBBAND_LINES..]);
+        }
+
+        let mut samples = [0.0; LINES];
+        for (slot, samples) in samples.chunks_exact_mut(SUBBANDS).enumerate() {
+            let input: [f32; SUBBANDS] = core::array::from_fn(|sb| subbands[sb][slot]);
+            self.synthesize(&input, samples);
+        }
+        samples
+    }
+
+    fn imdct_long(&self, lines: &[f32], block_type: u8) -> [f32; 36] {
+        core::array::from_fn(|i| {
+            let sum: f32 = self.imdct_long[i]
+                .iter()
+                .zip(lines)
+                .map(|(cos, line)| cos * line)
+                .sum();
+            sum * long_window(block_type, i)
+        })
+    }
+
+    /// The three windows of a short block, whose lines alternate in `lines`, overlapped at
+    /// 6, 12 and 18.
+    fn imdct_short(&self, lines: &[f32]) -> [f32; 36] {
+        let mut output = [0.0; 36];
+        for window in 0..3 {
+            for (i, cos) in self.imdct_short.iter().enumerate() {
+                let sum: f32 = (0..6).map(|k| cos[k] * lines[3 * k + window]).sum();
+                output[6 + 6 * window + i] += sum * (PI / 12.0 * (i as f32 + 0.5)).sin();
+            }
+        }
+        output
+    }
+
+    /// Runs one sample of each subband through the synthesis filterbank into 32 samples.
+    fn synthesize(&mut self, input: &[f32; SUBBANDS], output: &mut [f32]) {
+        self.v.copy_within(..1024 - 64, 64);
+        for (v, cos) in self.v[..64].iter_mut().zip(&self.matrix) {
+            *v = cos
+                .iter()
+                .zip(input)
+                .map(|(cos, sample)| cos * sample)
+                .sum();
+        }
+
+        for (j, sample) in output.iter_mut().enumerate() {
+            *sample = (0..16)
+                .map(|i| {
+                    // U takes the first and last 32 values of every other 64 of V.
+                    let v = self.v[64 * i + j + if i % 2 == 1 { 32 } else { 0 }];
+                    v * WINDOW[32 * i + j] as f32
+                })
+                .sum::<f32>()
+                / 65536.0;
+        }
+    }
+}
+
+/// Aliasing reduction: butterflies across the boundaries between the first `subbands`
+/// subbands.
+fn antialias(lines: &mut [f32; LINES], subbands: usize) {
+    for boundary in 1..subbands {
+        let middle = boundary * SUBBAND_LINES;
+        for (i, c) in ALIAS.iter().enumerate() {
+            let norm = (1.0 + c * c).sqrt();
+            let (cs, ca) = (1.0 / norm, c / norm);
+            let (upper, lower) = (lines[middle - 1 - i], lines[middle + i]);
+            lines[middle - 1 - i] = upper * cs - lower * ca;
+            lines[middle + i] = lower * cs + upper * ca;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer::Buffer, decoder, stream::AudioStream};
+
+    #[test]
+    fn test_synthesis() {
+        let stream = AudioStream::create_from_buffer(Buffer::create_buffer_from_file(
+            "mp3-examples/test_data_100kb.mp3",
+        ))
+        .unwrap();
+
+        let mut peak = 0.0f32;
+        for (frame, samples) in decoder::samples(&stream) {
+            assert_eq!(samples.len(), 2);
+            for channel in &samples {
+                assert_eq!(channel.len(), frame.header().samples_per_frame());
+                peak = channel
+                    .iter()
+                    .fold(peak, |peak, sample| peak.max(sample.abs()));
+            }
+        }
+        // A loud master, which slightly overshoots full scale once decoded.
+        assert!(peak > 0.9 && peak < 1.2, "{peak}");
+
+        let silence = Spectrum {
+            lines: [0.0; LINES],
+            short_start: LINES,
+        };
+        let mut synthesis = Synthesis::new();
+        assert!(synthesis
+            .process(&silence, &ChannelInfo::default())
+            .iter()
+            .all(|&sample| sample == 0.0));
+    }
+}
@@ -0,0 +1,224 @@
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+const ID3V2_HEADER_SIZE: usize = 10;
+const ID3V1_SIZE: usize = 128;
+const APE_FOOTER_SIZE: usize = 32;
+#[cfg(feature = "alloc")]
+const APE_VERSION: u32 = 2000;
+/// Flags of the APEv2 header and footer: the tag has a header, and this is it.
+#[cfg(feature = "alloc")]
+const APE_HAS_HEADER: u32 = 0x8000_0000;
+#[cfg(feature = "alloc")]
+const APE_IS_HEADER: u32 = 0x2000_0000;
+
+/// Length of the ID3v2 tag at the start of `data` (header, body and footer), or 0 if there is none.
+pub fn id3v2_length(data: &[u8]) -> usize {
+    id3v2_declared_length(data).min(data.len())
+}
+
+/// Length of the ID3v2 tag at the start of `data` as its header declares it, which may run past
+/// the end of `data`, or 0 if there is none.
+pub fn id3v2_declared_length(data: &[u8]) -> usize {
+    if data.len() < ID3V2_HEADER_SIZE || &data[..3] != b"ID3" {
+        return 0;
+    }
+
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let has_footer = data[5] & 0x10 == 0x10;
+
+    ID3V2_HEADER_SIZE + size + if has_footer { ID3V2_HEADER_SIZE } else { 0 }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+/// End of `data` without the ID3v1 tag, if it has one.
+fn id3v1_start(data: &[u8]) -> usize {
+    let end = data.len();
+    if end >= ID3V1_SIZE && &data[end - ID3V1_SIZE..end - ID3V1_SIZE + 3] == b"TAG" {
+        end - ID3V1_SIZE
+    } else {
+        end
+    }
+}
+
+/// Where the APEv2 tag (header, items and footer) is in `data`, before the ID3v1 tag if there is
+/// one. Empty at the end of the audio if there is no APEv2 tag, or if its footer gives a size
+/// that does not fit in `data` or does not lead to its header.
+pub fn ape_tag_range(data: &[u8]) -> Range<usize> {
+    let end = id3v1_start(data);
+
+    if end >= APE_FOOTER_SIZE
+        && &data[end - APE_FOOTER_SIZE..end - APE_FOOTER_SIZE + 8] == b"APETAGEX"
+    {
+        let footer = &data[end - APE_FOOTER_SIZE..end];
+        // The size in the footer covers the items and the footer, but not the optional header.
+        let size = read_u32(footer, 12) as usize;
+        let has_header = footer[23] & 0x80 == 0x80;
+        let length = size.saturating_add(if has_header { APE_FOOTER_SIZE } else { 0 });
+        if size < APE_FOOTER_SIZE || length > end {
+            return end..end;
+        }
+
+        let start = end - length;
+        if has_header && &data[start..start + 8] != b"APETAGEX" {
+            return end..end;
+        }
+
+        return start..end;
+    }
+
+    end..end
+}
+
+/// Length of the ID3v1 and APEv2 tags at the end of `data`, or 0 if there are none.
+pub fn trailing_tags_length(data: &[u8]) -> usize {
+    data.len() - ape_tag_range(data).start
+}
+
+/// An item of an APEv2 tag. The low bits of `flags` tell UTF-8 text (0) from binary data (2)
+/// and external references (4).
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApeItem {
+    pub key: String,
+    pub flags: u32,
+    pub value: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl ApeItem {
+    pub fn text(key: &str, value: &str) -> Self {
+        Self {
+            key: key.into(),
+            flags: 0,
+            value: value.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// The items of the APEv2 tag at the end of `data`, as far as they can be read.
+#[cfg(feature = "alloc")]
+pub fn ape_items(data: &[u8]) -> Vec<ApeItem> {
+    let range = ape_tag_range(data);
+    let tag = &data[range];
+    let mut items = Vec::new();
+    if tag.len() < APE_FOOTER_SIZE {
+        return items;
+    }
+
+    let footer = &tag[tag.len() - APE_FOOTER_SIZE..];
+    let count = read_u32(footer, 16) as usize;
+    let has_header = footer[23] & 0x80 == 0x80;
+    let mut pos = if has_header { APE_FOOTER_SIZE } else { 0 };
+    let end = tag.len() - APE_FOOTER_SIZE;
+
+    while items.len() < count && pos + 8 < end {
+        let length = read_u32(tag, pos) as usize;
+        let flags = read_u32(tag, pos + 4);
+        let Some(key_length) = tag[pos + 8..end].iter().position(|&byte| byte == 0) else {
+            break;
+        };
+        let key = &tag[pos + 8..pos + 8 + key_length];
+        let value_start = pos + 8 + key_length + 1;
+        let Some(value) = tag.get(value_start..value_start + length) else {
+            break;
+        };
+
+        items.push(ApeItem {
+            key: String::from_utf8_lossy(key).into(),
+            flags,
+            value: value.to_vec(),
+        });
+        pos = value_start + length;
+    }
+
+    items
+}
+
+/// An APEv2 tag, with header and footer, holding `items`.
+#[cfg(feature = "alloc")]
+pub fn ape_tag(items: &[ApeItem]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for item in items {
+        body.extend_from_slice(&(item.value.len() as u32).to_le_bytes());
+        body.extend_from_slice(&item.flags.to_le_bytes());
+        body.extend_from_slice(item.key.as_bytes());
+        body.push(0);
+        body.extend_from_slice(&item.value);
+    }
+
+    let frame = |flags: u32| {
+        let mut frame = Vec::with_capacity(APE_FOOTER_SIZE);
+        frame.extend_from_slice(b"APETAGEX");
+        frame.extend_from_slice(&APE_VERSION.to_le_bytes());
+        frame.extend_from_slice(&((body.len() + APE_FOOTER_SIZE) as u32).to_le_bytes());
+        frame.extend_from_slice(&(items.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&flags.to_le_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        frame
+    };
+
+    let mut tag = frame(APE_HAS_HEADER | APE_IS_HEADER);
+    tag.extend_from_slice(&body);
+    tag.extend(frame(APE_HAS_HEADER));
+    tag
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_id3v2_length() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x02\x01".to_vec();
+        data.resize(ID3V2_HEADER_SIZE + 257, 0);
+        data.extend_from_slice(&[0xff, 0xfb, 0x90, 0x64]);
+
+        assert_eq!(id3v2_length(&data), ID3V2_HEADER_SIZE + 257);
+        assert_eq!(id3v2_length(&data[ID3V2_HEADER_SIZE..]), 0);
+        assert_eq!(id3v2_length(&data[..100]), 100);
+        assert_eq!(id3v2_declared_length(&data[..100]), ID3V2_HEADER_SIZE + 257);
+    }
+
+    #[test]
+    fn test_trailing_tags_length() {
+        let mut data = vec![0xff, 0xfb, 0x90, 0x64];
+        data.extend_from_slice(b"TAG");
+        data.resize(4 + ID3V1_SIZE, 0);
+
+        assert_eq!(trailing_tags_length(&data), ID3V1_SIZE);
+        assert_eq!(trailing_tags_length(&data[..4]), 0);
+    }
+
+    #[test]
+    fn test_ape_tag() {
+        let items = [
+            ApeItem::text("MP3GAIN_UNDO", "+002,+002,N"),
+            ApeItem::text("Artist", "Someone"),
+        ];
+        let mut data = vec![0xff, 0xfb, 0x90, 0x64];
+        data.extend(ape_tag(&items));
+        let tag_length = data.len() - 4;
+        data.extend_from_slice(b"TAG");
+        data.resize(4 + tag_length + ID3V1_SIZE, 0);
+
+        assert_eq!(ape_tag_range(&data), 4..4 + tag_length);
+        assert_eq!(trailing_tags_length(&data), tag_length + ID3V1_SIZE);
+        assert_eq!(ape_items(&data), items);
+        assert!(ape_items(&data[..4]).is_empty());
+
+        // A corrupt size is no tag rather than one covering the audio.
+        let size_at = 4 + tag_length - APE_FOOTER_SIZE + 12;
+        for size in [u32::MAX, tag_length as u32 - APE_FOOTER_SIZE as u32 + 2] {
+            let mut data = data.clone();
+            data[size_at..size_at + 4].copy_from_slice(&size.to_le_bytes());
+            assert_eq!(ape_tag_range(&data), 4 + tag_length..4 + tag_length);
+        }
+    }
+}
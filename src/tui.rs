@@ -0,0 +1,395 @@
+use std::io;
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Paragraph, Row, Sparkline, SparklineBar, Table, TableState},
+    DefaultTerminal,
+};
+
+use crate::{frame::Frame, side_info::ChannelInfo};
+
+const HELP: &str =
+    "↑/↓ PgUp/PgDn Home/End: scroll  g: go to frame  /: search  n: next match  q: quit";
+
+/// What the keys typed go to.
+#[derive(Debug, PartialEq)]
+enum Input {
+    Normal,
+    Jump(String),
+    Search(String),
+}
+
+/// State of the frame browser, apart from the terminal.
+struct App<'a> {
+    frames: &'a [Frame],
+    /// Text of every row of the frame list, also what searches look through.
+    rows: Vec<[String; 5]>,
+    /// Bitrate and `main_data_begin` of every frame, for the sparklines.
+    bitrates: Vec<u64>,
+    reservoirs: Vec<u64>,
+    table: TableState,
+    /// First row shown in the frame list.
+    scroll: usize,
+    input: Input,
+    search: String,
+    status: String,
+}
+
+/// One letter per granule and channel: `L` long blocks, `>` start, `S` short, `M` mixed
+/// (long then short), `<` stop.
+fn block_types(frame: &Frame) -> String {
+    let granules = frame.header().granule_count();
+
+    frame.side_info().granules[..granules]
+        .iter()
+        .map(|granule| {
+            granule
+                .channels()
+                .iter()
+                .map(|channel: &ChannelInfo| {
+                    if !channel.windows_switching {
+                        return 'L';
+                    }
+                    match channel.block_type {
+                        1 => '>',
+                        2 if channel.mixed_block_flag => 'M',
+                        2 => 'S',
+                        _ => '<',
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `values` shrunk to `width` bars, each the largest value of the frames it covers.
+fn downsample(values: &[u64], width: usize) -> Vec<u64> {
+    if values.len() <= width || width == 0 {
+        return values.to_vec();
+    }
+
+    (0..width)
+        .map(|bar| {
+            let (start, end) = (bar * values.len() / width, (bar + 1) * values.len() / width);
+            values[start..end.max(start + 1)]
+                .iter()
+                .copied()
+                .max()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+impl<'a> App<'a> {
+    fn new(frames: &'a [Frame]) -> Self {
+        let mut samples = 0;
+        let rows = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let header = frame.header();
+                let time = samples as f64 / header.get_frequency().unwrap_or(1) as f64;
+                samples += header.samples_per_frame();
+
+                [
+                    i.to_string(),
+                    format!("{:#010x}", frame.offset_byte()),
+                    format!("{time:.3}"),
+                    header.get_bitrate().unwrap_or(0).to_string(),
+                    block_types(frame),
+                ]
+            })
+            .collect();
+
+        Self {
+            frames,
+            rows,
+            bitrates: frames
+                .iter()
+                .map(|frame| frame.header().get_bitrate().unwrap_or(0) as u64)
+                .collect(),
+            reservoirs: frames
+                .iter()
+                .map(|frame| frame.side_info().main_data_begin as u64)
+                .collect(),
+            table: TableState::default().with_selected(Some(0)),
+            scroll: 0,
+            input: Input::Normal,
+            search: String::new(),
+            status: String::new(),
+        }
+    }
+
+    fn selected(&self) -> usize {
+        self.table.selected().unwrap_or(0)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.table
+            .select(Some(index.min(self.frames.len().saturating_sub(1))));
+    }
+
+    /// Selects the next frame after the selected one, wrapping around, whose row contains the
+    /// search text.
+    fn find_next(&mut self) {
+        let query = self.search.to_lowercase();
+        let count = self.rows.len();
+        let found = (1..=count)
+            .map(|step| (self.selected() + step) % count)
+            .find(|&i| {
+                self.rows[i]
+                    .iter()
+                    .any(|cell| cell.to_lowercase().contains(&query))
+            });
+
+        match found {
+            Some(i) => {
+                self.select(i);
+                self.status.clear();
+            }
+            None => self.status = format!("`{}` not found", self.search),
+        }
+    }
+
+    /// Handles a key press; returns whether to quit.
+    fn handle_key(&mut self, key: KeyCode, page: usize) -> bool {
+        match &mut self.input {
+            Input::Jump(text) | Input::Search(text) => match key {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Esc => self.input = Input::Normal,
+                KeyCode::Enter => match std::mem::replace(&mut self.input, Input::Normal) {
+                    Input::Jump(text) => match text.parse() {
+                        Ok(index) => self.select(index),
+                        Err(_) => self.status = format!("`{text}` is not a frame number"),
+                    },
+                    Input::Search(text) => {
+                        self.search = text;
+                        self.find_next();
+                    }
+                    Input::Normal => {}
+                },
+                _ => {}
+            },
+            Input::Normal => match key {
+                KeyCode::Char('q') | KeyCode::Esc => return true,
+                KeyCode::Down | KeyCode::Char('j') => self.select(self.selected() + 1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(self.selected().saturating_sub(1)),
+                KeyCode::PageDown => self.select(self.selected() + page),
+                KeyCode::PageUp => self.select(self.selected().saturating_sub(page)),
+                KeyCode::Home => self.select(0),
+                KeyCode::End => self.select(self.frames.len()),
+                KeyCode::Char('g') => self.input = Input::Jump(String::new()),
+                KeyCode::Char('/') => self.input = Input::Search(String::new()),
+                KeyCode::Char('n') if !self.search.is_empty() => self.find_next(),
+                _ => {}
+            },
+        }
+
+        false
+    }
+
+    /// Scrolls the frame list, `height` rows high, just enough to show the selected row.
+    fn scroll_to_selected(&mut self, height: usize) {
+        let selected = self.selected();
+        self.scroll = self
+            .scroll
+            .clamp((selected + 1).saturating_sub(height), selected);
+    }
+
+    fn sparkline<'b>(&self, title: &'b str, values: &[u64], area: Rect) -> Sparkline<'b> {
+        let width = area.width.saturating_sub(2) as usize;
+        let bars = downsample(values, width);
+        let marked = self.selected() * bars.len() / values.len().max(1);
+
+        Sparkline::default()
+            .block(Block::bordered().title(title))
+            .data(bars.into_iter().enumerate().map(|(i, value)| {
+                let bar = SparklineBar::from(value);
+                if i == marked {
+                    bar.style(Some(Style::default().fg(Color::Yellow)))
+                } else {
+                    bar
+                }
+            }))
+    }
+
+    fn draw(&mut self, terminal: &mut DefaultTerminal) -> io::Result<usize> {
+        let mut page = 1;
+
+        terminal.draw(|term| {
+            let [main, bitrate, reservoir, footer] = Layout::vertical([
+                Constraint::Min(10),
+                Constraint::Length(5),
+                Constraint::Length(5),
+                Constraint::Length(1),
+            ])
+            .areas(term.area());
+            let [list, details] =
+                Layout::horizontal([Constraint::Length(48), Constraint::Min(40)]).areas(main);
+            let [header, granules] =
+                Layout::vertical([Constraint::Length(19), Constraint::Min(5)]).areas(details);
+            let [granule0, granule1] =
+                Layout::horizontal([Constraint::Ratio(1, 2); 2]).areas(granules);
+            page = list.height.saturating_sub(3).max(1) as usize;
+
+            // Only the rows in view are handed to the table.
+            self.scroll_to_selected(page);
+            let end = (self.scroll + page).min(self.rows.len());
+            let rows = self.rows[self.scroll..end]
+                .iter()
+                .map(|row| Row::new(row.iter().map(String::as_str)));
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Length(6),
+                    Constraint::Length(10),
+                    Constraint::Length(8),
+                    Constraint::Length(4),
+                    Constraint::Length(11),
+                ],
+            )
+            .header(
+                Row::new(["Frame", "Offset", "Time", "kb/s", "Blocks"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::bordered().title("Frames (L long S short M mixed > start < stop)"))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            let mut state = TableState::default().with_selected(self.selected() - self.scroll);
+            term.render_stateful_widget(table, list, &mut state);
+
+            let frame = &self.frames[self.selected()];
+            let side_info = frame.side_info();
+            let text = format!(
+                "{}\n\t main_data_begin: {}\n\t private_bits: {:#b}\n\t scfsi: {:#010b}\n\t crc: {}",
+                frame.header(),
+                side_info.main_data_begin,
+                side_info.private_bits,
+                side_info.scfsi,
+                frame.crc().map_or("none".to_string(), |crc| format!("{crc:#06x}")),
+            )
+            .replace('\t', " ");
+            term.render_widget(
+                Paragraph::new(text).block(Block::bordered().title(format!(
+                    "Frame {} at {:#x}, {} bytes",
+                    self.selected(),
+                    frame.offset_byte(),
+                    frame.length_byte()
+                ))),
+                header,
+            );
+
+            for (i, area) in [granule0, granule1].into_iter().enumerate() {
+                let text = side_info.granules[i].to_string().replace('\t', " ");
+                let text = text.trim_start_matches('\n').to_string();
+                term.render_widget(
+                    Paragraph::new(text).block(Block::bordered().title(format!("Granule {i}"))),
+                    area,
+                );
+            }
+
+            term.render_widget(self.sparkline("Bitrate", &self.bitrates, bitrate), bitrate);
+            term.render_widget(
+                self.sparkline("Reservoir (main_data_begin)", &self.reservoirs, reservoir),
+                reservoir,
+            );
+
+            let footer_text = match &self.input {
+                Input::Jump(text) => format!("Go to frame: {text}"),
+                Input::Search(text) => format!("Search: {text}"),
+                Input::Normal if !self.status.is_empty() => self.status.clone(),
+                Input::Normal => HELP.to_string(),
+            };
+            term.render_widget(Paragraph::new(footer_text), footer);
+        })?;
+
+        Ok(page)
+    }
+}
+
+/// Browses `frames` in the terminal until `q` is pressed.
+pub fn browse(frames: &[Frame]) -> io::Result<()> {
+    if frames.is_empty() {
+        return Ok(());
+    }
+
+    let mut terminal = ratatui::init();
+    let mut app = App::new(frames);
+
+    let result = loop {
+        let page = match app.draw(&mut terminal) {
+            Ok(page) => page,
+            Err(err) => break Err(err),
+        };
+
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if app.handle_key(key.code, page) {
+                    break Ok(());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_browser_keys() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let frames = buffer.extract_frames();
+        let mut app = App::new(&frames);
+
+        assert_eq!(app.rows[1][1], "0x000001a1");
+        assert_eq!(app.rows[1][4], "LL >>");
+
+        app.handle_key(KeyCode::PageDown, 20);
+        app.handle_key(KeyCode::Up, 20);
+        assert_eq!(app.selected(), 19);
+        app.scroll_to_selected(10);
+        assert_eq!(app.scroll, 10);
+        app.select(12);
+        app.scroll_to_selected(10);
+        assert_eq!(app.scroll, 10);
+        app.select(3);
+        app.scroll_to_selected(10);
+        assert_eq!(app.scroll, 3);
+        app.select(19);
+
+        for key in [KeyCode::Char('g'), KeyCode::Char('8'), KeyCode::Char('7')] {
+            app.handle_key(key, 20);
+        }
+        assert_eq!(app.input, Input::Jump("87".into()));
+        app.handle_key(KeyCode::Enter, 20);
+        assert_eq!(app.selected(), 87);
+
+        // Searching wraps around to the first row mentioning 320.
+        let first = app
+            .rows
+            .iter()
+            .position(|row| row.iter().any(|cell| cell.contains("320")))
+            .unwrap();
+        app.handle_key(KeyCode::End, 20);
+        for key in "/320".chars().map(KeyCode::Char) {
+            app.handle_key(key, 20);
+        }
+        app.handle_key(KeyCode::Enter, 20);
+        assert_eq!(app.selected(), first);
+
+        assert_eq!(downsample(&[1, 5, 2, 8, 3, 1], 3), [5, 8, 3]);
+        assert!(app.handle_key(KeyCode::Char('q'), 20));
+    }
+}
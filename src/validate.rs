@@ -0,0 +1,290 @@
+use std::fmt::Display;
+
+use crate::{
+    buffer::Buffer, error::ErrorType, frame::Frame, header::Header, tag, xing::XingHeader,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Anomaly {
+    Junk {
+        length: usize,
+    },
+    TruncatedFrame {
+        length: usize,
+        available: usize,
+    },
+    StreamChange {
+        field: &'static str,
+        from: String,
+        to: String,
+    },
+    InvalidSideInfo(ErrorType),
+    ReservoirUnderflow {
+        main_data_begin: u16,
+        available: usize,
+    },
+    CrcMismatch {
+        stored: u16,
+        computed: u16,
+    },
+    XingFrameCount {
+        xing: u32,
+        actual: u32,
+    },
+    XingByteCount {
+        xing: u32,
+        actual: u32,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub offset: usize,
+    pub anomaly: Anomaly,
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Junk { length } => write!(f, "{length} bytes of junk before the next frame"),
+            Self::TruncatedFrame { length, available } => {
+                write!(f, "truncated frame: {available} of {length} bytes")
+            }
+            Self::StreamChange { field, from, to } => write!(f, "{field} changes from {from} to {to}"),
+            Self::InvalidSideInfo(err) => write!(f, "invalid side info: {err:?}"),
+            Self::ReservoirUnderflow {
+                main_data_begin,
+                available,
+            } => write!(
+                f,
+                "bit reservoir underflow: main_data_begin is {main_data_begin} but only {available} unused bytes precede the frame"
+            ),
+            Self::CrcMismatch { stored, computed } => {
+                write!(f, "CRC mismatch: stored {stored:#06x}, computed {computed:#06x}")
+            }
+            Self::XingFrameCount { xing, actual } => {
+                write!(f, "Xing header counts {xing} frames, the stream has {actual}")
+            }
+            Self::XingByteCount { xing, actual } => {
+                write!(f, "Xing header counts {xing} bytes, the stream has {actual}")
+            }
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#010x}: {}", self.offset, self.anomaly)
+    }
+}
+
+fn stream_changes(previous: &Header, header: &Header) -> Vec<Anomaly> {
+    let mut changes = Vec::new();
+
+    if previous.version != header.version {
+        changes.push(Anomaly::StreamChange {
+            field: "version",
+            from: previous.version.to_string(),
+            to: header.version.to_string(),
+        });
+    }
+    if previous.layer != header.layer {
+        changes.push(Anomaly::StreamChange {
+            field: "layer",
+            from: previous.layer.to_string(),
+            to: header.layer.to_string(),
+        });
+    }
+    if previous.frequency != header.frequency {
+        changes.push(Anomaly::StreamChange {
+            field: "sample rate",
+            from: format!("{}Hz", previous.get_frequency().unwrap_or(0)),
+            to: format!("{}Hz", header.get_frequency().unwrap_or(0)),
+        });
+    }
+
+    changes
+}
+
+/// Walks every frame of `buffer` and reports, by byte offset, everything that keeps the stream
+/// from being a clean sequence of frames.
+pub fn validate(buffer: &mut Buffer) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let audio_end = buffer.data.len() - tag::trailing_tags_length(&buffer.data);
+    let mut expected = tag::id3v2_length(&buffer.data);
+    let mut search = expected;
+
+    let mut previous: Option<Header> = None;
+    let mut xing: Option<(usize, XingHeader)> = None;
+    // Main data bytes before the next frame that no granule has used.
+    let mut reservoir = 0;
+    let mut frames = 0;
+
+    while search < audio_end {
+        buffer.pos = search * 8;
+        let Some(offset) = buffer
+            .set_pos_next_frame()
+            .ok()
+            .filter(|offset| offset + 4 <= audio_end)
+        else {
+            issues.push(Issue {
+                offset: expected,
+                anomaly: Anomaly::Junk {
+                    length: audio_end - expected,
+                },
+            });
+            break;
+        };
+
+        let parsed = Header::create_from_buffer(buffer).and_then(|header| {
+            header.validate_header()?;
+            Ok((buffer.frame_length(&header)?, header))
+        });
+        let Ok((length, header)) = parsed else {
+            search = offset + 1;
+            continue;
+        };
+
+        if offset > expected {
+            issues.push(Issue {
+                offset: expected,
+                anomaly: Anomaly::Junk {
+                    length: offset - expected,
+                },
+            });
+        }
+
+        if offset + length > audio_end {
+            issues.push(Issue {
+                offset,
+                anomaly: Anomaly::TruncatedFrame {
+                    length,
+                    available: audio_end - offset,
+                },
+            });
+            break;
+        }
+
+        if let Some(previous) = &previous {
+            for anomaly in stream_changes(previous, &header) {
+                issues.push(Issue { offset, anomaly });
+                reservoir = 0;
+            }
+        }
+
+        buffer.pos = offset * 8;
+        match Frame::create_from_buffer(buffer) {
+            Err(err) => issues.push(Issue {
+                offset,
+                anomaly: Anomaly::InvalidSideInfo(err),
+            }),
+            Ok(frame) => {
+                let data = buffer.frame_data(&frame);
+
+                if previous.is_none() {
+                    if let Some(header) = XingHeader::parse(data, frame.header()) {
+                        xing = Some((offset, header));
+                    }
+                }
+
+                if let Some(stored) = frame.crc() {
+                    let computed = frame.compute_crc(data);
+                    if computed != stored {
+                        issues.push(Issue {
+                            offset,
+                            anomaly: Anomaly::CrcMismatch { stored, computed },
+                        });
+                    }
+                }
+
+                let is_xing = xing.as_ref().is_some_and(|(xing, _)| *xing == offset);
+                if !is_xing {
+                    let main_data_begin = frame.side_info().main_data_begin;
+                    if main_data_begin as usize > reservoir {
+                        issues.push(Issue {
+                            offset,
+                            anomaly: Anomaly::ReservoirUnderflow {
+                                main_data_begin,
+                                available: reservoir,
+                            },
+                        });
+                    }
+
+                    let used = frame.side_info().main_data_bits().div_ceil(8);
+                    reservoir =
+                        (main_data_begin as usize + frame.main_data_length()).saturating_sub(used);
+                    frames += 1;
+                }
+            }
+        }
+
+        previous = Some(header);
+        expected = offset + length;
+        search = expected;
+    }
+
+    if let Some((offset, header)) = xing {
+        if let Some(count) = header.frames.filter(|count| *count != frames) {
+            issues.push(Issue {
+                offset,
+                anomaly: Anomaly::XingFrameCount {
+                    xing: count,
+                    actual: frames,
+                },
+            });
+        }
+
+        let actual = (expected - offset) as u32;
+        if let Some(bytes) = header.bytes.filter(|bytes| *bytes != actual) {
+            issues.push(Issue {
+                offset,
+                anomaly: Anomaly::XingByteCount {
+                    xing: bytes,
+                    actual,
+                },
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{buffer, stream::AudioStream};
+
+    #[test]
+    fn test_validate_sample_and_truncated_copy() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        assert_eq!(validate(&mut buffer), vec![]);
+
+        // A frame pointing back over bytes an earlier granule already used.
+        let stream = AudioStream::create_from_buffer(buffer.share()).unwrap();
+        let offset = stream.frames()[10].offset_byte();
+        let mut data = buffer.data.to_vec();
+        buffer::write_bits(&mut data, (offset + 4) * 8, 9, 511);
+        let issues = validate(&mut Buffer::create_buffer_from_data(data));
+        assert!(matches!(
+            issues[0],
+            Issue {
+                offset: at,
+                anomaly: Anomaly::ReservoirUnderflow {
+                    main_data_begin: 511,
+                    available,
+                },
+            } if at == offset && available < 511
+        ));
+
+        let mut data = buffer.data.to_vec();
+        data.truncate(50_000);
+        let issues = validate(&mut Buffer::create_buffer_from_data(data));
+
+        assert!(matches!(issues[0].anomaly, Anomaly::TruncatedFrame { .. }));
+        assert!(matches!(
+            issues[1].anomaly,
+            Anomaly::XingFrameCount { xing: 153, .. }
+        ));
+    }
+}
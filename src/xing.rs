@@ -0,0 +1,430 @@
+use crate::{crc, error::ErrorType, header::Header};
+
+const FLAG_FRAMES: u32 = 0x1;
+const FLAG_BYTES: u32 = 0x2;
+const FLAG_TOC: u32 = 0x4;
+const FLAG_QUALITY: u32 = 0x8;
+
+const TOC_SIZE: usize = 100;
+const VBRI_OFFSET: usize = 36;
+const VBRI_HEADER_SIZE: usize = 26;
+const LAME_HEADER_SIZE: usize = 36;
+const LAME_MUSIC_CRC_OFFSET: usize = 32;
+const LAME_CRC_OFFSET: usize = 34;
+
+/// Largest value of the 12-bit encoder delay and padding fields of the LAME header.
+pub const MAX_LAME_DELAY: usize = 0xfff;
+
+/// LAME extension stored right after the Xing/Info fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LameHeader {
+    pub encoder: String,
+    pub encoder_delay: u16,
+    pub encoder_padding: u16,
+    pub music_length: u32,
+    pub music_crc: u16,
+    raw: [u8; LAME_HEADER_SIZE],
+}
+
+/// Xing (VBR) or Info (CBR) header stored in the first, silent frame of a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XingHeader {
+    pub is_vbr: bool,
+    pub frames: Option<u32>,
+    pub bytes: Option<u32>,
+    pub toc: Option<[u8; TOC_SIZE]>,
+    pub quality: Option<u32>,
+    pub lame: Option<LameHeader>,
+}
+
+/// Fraunhofer VBRI header, stored 32 bytes after the header of the first, silent frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VbriHeader {
+    pub version: u16,
+    pub delay: u16,
+    pub quality: u16,
+    pub bytes: u32,
+    pub frames: u32,
+    pub frames_per_entry: u16,
+    /// Size in bytes of every run of `frames_per_entry` frames.
+    pub toc: Vec<u32>,
+}
+
+impl LameHeader {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < LAME_HEADER_SIZE || !matches!(&data[..4], b"LAME" | b"Lavf" | b"Lavc") {
+            return None;
+        }
+
+        let mut raw = [0; LAME_HEADER_SIZE];
+        raw.copy_from_slice(&data[..LAME_HEADER_SIZE]);
+
+        let lame = Self {
+            encoder: String::from_utf8_lossy(&raw[..9]).trim_end().to_string(),
+            encoder_delay: ((raw[21] as u16) << 4) | (raw[22] as u16 >> 4),
+            encoder_padding: ((raw[22] as u16 & 0xf) << 8) | raw[23] as u16,
+            music_length: u32::from_be_bytes([raw[28], raw[29], raw[30], raw[31]]),
+            music_crc: u16::from_be_bytes([raw[32], raw[33]]),
+            raw: [0; LAME_HEADER_SIZE],
+        };
+
+        // Only the fields that are not decoded above are kept raw.
+        raw[21..24].fill(0);
+        raw[28..36].fill(0);
+
+        Some(Self { raw, ..lame })
+    }
+
+    /// Encoded header without the tag CRC, which covers the whole frame up to it.
+    fn to_bytes(&self) -> [u8; LAME_HEADER_SIZE] {
+        let mut raw = self.raw;
+
+        raw[21] = (self.encoder_delay >> 4) as u8;
+        raw[22] = ((self.encoder_delay & 0xf) << 4) as u8 | (self.encoder_padding >> 8) as u8 & 0xf;
+        raw[23] = self.encoder_padding as u8;
+        raw[28..32].copy_from_slice(&self.music_length.to_be_bytes());
+        raw[LAME_MUSIC_CRC_OFFSET..LAME_CRC_OFFSET].copy_from_slice(&self.music_crc.to_be_bytes());
+
+        raw
+    }
+}
+
+impl VbriHeader {
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let data = frame.get(VBRI_OFFSET..)?;
+        if data.len() < VBRI_HEADER_SIZE || &data[..4] != b"VBRI" {
+            return None;
+        }
+
+        let read_u16 = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let entries = read_u16(18) as usize;
+        let scale = read_u16(20) as u32;
+        let entry_size = read_u16(22) as usize;
+
+        let table = data.get(VBRI_HEADER_SIZE..VBRI_HEADER_SIZE + entries * entry_size)?;
+        let toc = match entry_size {
+            1..=4 => table
+                .chunks_exact(entry_size)
+                .map(|entry| {
+                    entry
+                        .iter()
+                        .fold(0, |value, byte| (value << 8) | *byte as u32)
+                        * scale
+                })
+                .collect(),
+            _ => return None,
+        };
+
+        Some(Self {
+            version: read_u16(4),
+            delay: read_u16(6),
+            quality: read_u16(8),
+            bytes: u32::from_be_bytes([data[10], data[11], data[12], data[13]]),
+            frames: u32::from_be_bytes([data[14], data[15], data[16], data[17]]),
+            frames_per_entry: read_u16(24),
+            toc,
+        })
+    }
+
+    /// `(sample, byte)` pairs mapping positions in the stream to offsets from the start of the
+    /// VBRI frame, one per table entry.
+    pub fn seek_points(&self, samples_per_frame: usize) -> Option<Vec<(u64, u64)>> {
+        if self.toc.is_empty() || self.frames_per_entry == 0 {
+            return None;
+        }
+
+        let total = self.frames as u64 * samples_per_frame as u64;
+        let step = self.frames_per_entry as u64 * samples_per_frame as u64;
+
+        let mut points = vec![(0, 0)];
+        let mut bytes = 0;
+        for (i, size) in self.toc.iter().enumerate() {
+            bytes += *size as u64;
+            points.push((((i as u64 + 1) * step).min(total), bytes));
+        }
+
+        Some(points)
+    }
+}
+
+impl XingHeader {
+    fn tag_offset(header: &Header) -> usize {
+        4 + if header.error_protection { 2 } else { 0 } + header.side_info_length()
+    }
+
+    pub fn parse(frame: &[u8], header: &Header) -> Option<Self> {
+        let mut offset = Self::tag_offset(header);
+        let tag = frame.get(offset..offset + 8)?;
+
+        let is_vbr = match &tag[..4] {
+            b"Xing" => true,
+            b"Info" => false,
+            _ => return None,
+        };
+        let flags = u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]);
+        offset += 8;
+
+        let mut read_u32 = |flag: u32| -> Option<Option<u32>> {
+            if flags & flag == 0 {
+                return Some(None);
+            }
+            let bytes = frame.get(offset..offset + 4)?;
+            offset += 4;
+            Some(Some(u32::from_be_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ])))
+        };
+
+        let frames = read_u32(FLAG_FRAMES)?;
+        let bytes = read_u32(FLAG_BYTES)?;
+
+        let toc = if flags & FLAG_TOC != 0 {
+            let mut toc = [0; TOC_SIZE];
+            toc.copy_from_slice(frame.get(offset..offset + TOC_SIZE)?);
+            offset += TOC_SIZE;
+            Some(toc)
+        } else {
+            None
+        };
+
+        let quality = if flags & FLAG_QUALITY != 0 {
+            let bytes = frame.get(offset..offset + 4)?;
+            offset += 4;
+            Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        } else {
+            None
+        };
+
+        Some(Self {
+            is_vbr,
+            frames,
+            bytes,
+            toc,
+            quality,
+            lame: LameHeader::parse(&frame[offset..]),
+        })
+    }
+
+    /// `(sample, byte)` pairs mapping positions in the stream to offsets from the start of the
+    /// Xing frame: one per percent of the duration, plus the end of the stream. The table has
+    /// a resolution of 1/256 of the file size.
+    pub fn seek_points(&self, samples_per_frame: usize) -> Option<Vec<(u64, u64)>> {
+        let (toc, frames, bytes) = (self.toc.as_ref()?, self.frames?, self.bytes?);
+        let total = frames as u64 * samples_per_frame as u64;
+
+        let mut points: Vec<(u64, u64)> = toc
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                (
+                    total * i as u64 / TOC_SIZE as u64,
+                    *entry as u64 * bytes as u64 / 256,
+                )
+            })
+            .collect();
+        points.push((total, bytes as u64));
+
+        Some(points)
+    }
+
+    fn encoded_length(&self) -> usize {
+        8 + self.frames.map_or(0, |_| 4)
+            + self.bytes.map_or(0, |_| 4)
+            + self.toc.map_or(0, |_| TOC_SIZE)
+            + self.quality.map_or(0, |_| 4)
+            + self.lame.as_ref().map_or(0, |_| LAME_HEADER_SIZE)
+    }
+
+    /// Encodes the header into a silent frame with the same version, sample rate and channel
+    /// mode as `header`, using the lowest bitrate that fits. `header_bytes` are the 4 raw bytes
+    /// of `header`.
+    pub fn to_frame(&self, header: &Header, header_bytes: &[u8]) -> Result<Vec<u8>, ErrorType> {
+        let mut info_header = header.clone();
+        info_header.error_protection = false;
+        info_header.padding_bit = false;
+
+        let needed = Self::tag_offset(&info_header) + self.encoded_length();
+        let bitrate = (1..15)
+            .find(|bitrate| {
+                info_header.bitrate = *bitrate;
+                info_header
+                    .frame_length()
+                    .is_ok_and(|length| length >= needed)
+            })
+            .ok_or(ErrorType::Overflow)?;
+
+        let mut frame = vec![0; info_header.frame_length()?];
+        frame[0] = 0xff;
+        frame[1] = header_bytes[1] | 1;
+        frame[2] = (bitrate << 4) | (header_bytes[2] & 0x0d);
+        frame[3] = header_bytes[3];
+
+        let mut offset = Self::tag_offset(&info_header);
+        let mut write = |bytes: &[u8]| {
+            frame[offset..offset + bytes.len()].copy_from_slice(bytes);
+            offset += bytes.len();
+        };
+
+        write(if self.is_vbr { b"Xing" } else { b"Info" });
+
+        let flags = self.frames.map_or(0, |_| FLAG_FRAMES)
+            | self.bytes.map_or(0, |_| FLAG_BYTES)
+            | self.toc.map_or(0, |_| FLAG_TOC)
+            | self.quality.map_or(0, |_| FLAG_QUALITY);
+        write(&flags.to_be_bytes());
+
+        for value in [self.frames, self.bytes].into_iter().flatten() {
+            write(&value.to_be_bytes());
+        }
+        if let Some(toc) = &self.toc {
+            write(toc);
+        }
+        if let Some(quality) = self.quality {
+            write(&quality.to_be_bytes());
+        }
+
+        if let Some(lame) = &self.lame {
+            write(&lame.to_bytes());
+
+            let lame_offset = needed - LAME_HEADER_SIZE;
+            let tag_crc = crc::crc16_lame(&frame[..lame_offset + LAME_CRC_OFFSET]);
+            frame[lame_offset + LAME_CRC_OFFSET..lame_offset + LAME_HEADER_SIZE]
+                .copy_from_slice(&tag_crc.to_be_bytes());
+        }
+
+        Ok(frame)
+    }
+
+    /// Rewrites the music CRC of the LAME header in `frame`, the Xing/Info frame this header was
+    /// parsed from, for the audio frames `body` that follow it, and the tag CRC with it. Frames
+    /// without a LAME header are left as they are.
+    pub fn update_music_crc(&self, frame: &mut [u8], header: &Header, body: &[u8]) {
+        if self.lame.is_none() {
+            return;
+        }
+
+        let lame_offset = Self::tag_offset(header) + self.encoded_length() - LAME_HEADER_SIZE;
+        frame[lame_offset + LAME_MUSIC_CRC_OFFSET..lame_offset + LAME_CRC_OFFSET]
+            .copy_from_slice(&crc::crc16_lame(body).to_be_bytes());
+        let tag_crc = crc::crc16_lame(&frame[..lame_offset + LAME_CRC_OFFSET]);
+        frame[lame_offset + LAME_CRC_OFFSET..lame_offset + LAME_HEADER_SIZE]
+            .copy_from_slice(&tag_crc.to_be_bytes());
+    }
+
+    /// Computes the header for an Info frame placed right before `body`, whose frames start at
+    /// `frame_offsets` (relative to `body`). `header` is the header of the first frame; quality
+    /// and LAME fields are taken from `base` when present.
+    pub fn describe(
+        body: &[u8],
+        frame_offsets: &[usize],
+        header: &Header,
+        base: Option<&XingHeader>,
+    ) -> Result<Self, ErrorType> {
+        let first = *frame_offsets.first().ok_or(ErrorType::NoFrames)?;
+        let bitrate = |offset: usize| body[offset + 2] >> 4;
+
+        let mut xing = XingHeader {
+            is_vbr: frame_offsets
+                .iter()
+                .any(|offset| bitrate(*offset) != bitrate(first)),
+            frames: Some(frame_offsets.len() as u32),
+            bytes: Some(0),
+            toc: Some([0; TOC_SIZE]),
+            quality: base.and_then(|base| base.quality),
+            lame: base.and_then(|base| base.lame.clone()),
+        };
+
+        let info_length = xing.to_frame(header, &body[first..])?.len();
+        let total = info_length + body.len();
+
+        let mut toc = [0; TOC_SIZE];
+        for (i, entry) in toc.iter_mut().enumerate() {
+            let frame_offset = info_length + frame_offsets[i * frame_offsets.len() / TOC_SIZE];
+            *entry = (frame_offset * 256 / total).min(255) as u8;
+        }
+
+        xing.bytes = Some(total as u32);
+        xing.toc = Some(toc);
+        if let Some(lame) = xing.lame.as_mut() {
+            lame.music_length = total as u32;
+            lame.music_crc = crc::crc16_lame(body);
+        }
+
+        Ok(xing)
+    }
+
+    /// Writes `audio` frames behind a freshly computed Xing/Info frame. `header` is the header
+    /// of the first audio frame; quality and LAME fields are taken from `base` when present.
+    pub fn build_stream(
+        audio: &[&[u8]],
+        header: &Header,
+        base: Option<&XingHeader>,
+    ) -> Result<Vec<u8>, ErrorType> {
+        let body = audio.concat();
+
+        let mut frame_offsets = Vec::with_capacity(audio.len());
+        let mut offset = 0;
+        for frame in audio {
+            frame_offsets.push(offset);
+            offset += frame.len();
+        }
+
+        let xing = Self::describe(&body, &frame_offsets, header, base)?;
+
+        let mut stream = xing.to_frame(header, &body)?;
+        stream.extend_from_slice(&body);
+
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_xing_from_frame() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let frames = buffer.extract_frames();
+        let xing = XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+
+        assert!(xing.is_vbr);
+        assert_eq!(xing.frames, Some(153));
+        assert_eq!(xing.bytes, Some(buffer.data.len() as u32));
+
+        let lame = xing.lame.unwrap();
+        assert_eq!(lame.encoder, "LAME3.100");
+        assert_eq!(lame.encoder_delay, 576);
+        assert_eq!(lame.encoder_padding, 679);
+    }
+
+    #[test]
+    fn test_build_stream_round_trip() {
+        let mut buffer = Buffer::create_buffer_from_file("mp3-examples/test_data_100kb.mp3");
+        let frames = buffer.extract_frames();
+        let xing = XingHeader::parse(buffer.frame_data(&frames[0]), frames[0].header()).unwrap();
+
+        let audio: Vec<&[u8]> = frames[1..]
+            .iter()
+            .map(|frame| buffer.frame_data(frame))
+            .collect();
+        let stream = XingHeader::build_stream(&audio, frames[1].header(), Some(&xing)).unwrap();
+
+        let mut rebuilt = Buffer::create_buffer_from_data(stream);
+        let rebuilt_frames = rebuilt.extract_frames();
+        let rebuilt_xing = XingHeader::parse(
+            rebuilt.frame_data(&rebuilt_frames[0]),
+            rebuilt_frames[0].header(),
+        )
+        .unwrap();
+
+        assert_eq!(rebuilt_frames.len(), frames.len());
+        assert_eq!(rebuilt_xing.frames, xing.frames);
+        assert_eq!(rebuilt_xing.bytes, Some(rebuilt.data.len() as u32));
+        assert_eq!(
+            rebuilt_xing.lame.unwrap().music_crc,
+            xing.lame.unwrap().music_crc
+        );
+    }
+}